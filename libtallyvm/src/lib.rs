@@ -4,18 +4,19 @@ use std::{
     mem,
     path::{Path, PathBuf},
     ptr,
-    sync::{Mutex, OnceLock},
 };
 
 use seda_wasm_vm::{
+    compatibility::{check_compatibility, CompatibilityReport},
+    gas_schedule::{GasScheduleRegistry, GasScheduleSelector, GasScheduleVersion},
     init_logger,
     start_runtime,
-    vm::{ExitInfo, VmCallData, VmResult, VmType, WasmId},
+    vm::{ExitInfo, VmCallData, VmResult, VmType, WasmEngine, WasmId},
     RuntimeContext,
     RuntimeError,
 };
 
-use crate::errors::Result;
+use crate::errors::{Result, TallyVmError};
 
 mod errors;
 
@@ -53,14 +54,17 @@ impl From<ExitInfo> for FfiExitInfo {
 #[derive(Debug)]
 #[repr(C)]
 pub struct FfiVmResult {
-    stdout_ptr: *const *const c_char,
-    stdout_len: usize,
-    stderr_ptr: *const *const c_char,
-    stderr_len: usize,
-    result_ptr: *const u8,
-    result_len: usize,
-    exit_info:  FfiExitInfo,
-    gas_used:   u64,
+    stdout_ptr:  *const *const c_char,
+    stdout_len:  usize,
+    stderr_ptr:  *const *const c_char,
+    stderr_len:  usize,
+    result_ptr:  *const u8,
+    result_len:  usize,
+    exit_info:   FfiExitInfo,
+    gas_used:    u64,
+    call_id:     *const c_char,
+    /// Host ABI version the program was built against, 0 if it uses no SEDA host imports
+    abi_version: u32,
 }
 
 impl FfiVmResult {
@@ -79,6 +83,12 @@ impl FfiVmResult {
         let stderr_len = boxed_slice.len();
         mem::forget(boxed_slice);
 
+        let call_id = vm_result
+            .call_id
+            .map_or(ptr::null(), |call_id| safe_string_to_cstring(&call_id).into_raw());
+
+        let abi_version = vm_result.abi_version.map_or(0, |abi_version| abi_version.number());
+
         let result = vm_result.result.unwrap_or_default().into_boxed_slice();
         let result_ptr = result.as_ptr();
         let result_len = result.len();
@@ -99,6 +109,8 @@ impl FfiVmResult {
                 stdout_len,
                 stderr_ptr,
                 stderr_len,
+                call_id,
+                abi_version,
             }
         } else {
             mem::forget(result);
@@ -111,9 +123,38 @@ impl FfiVmResult {
                 result_len,
                 exit_info: vm_result.exit_info.into(),
                 gas_used: vm_result.gas_used,
+                call_id,
+                abi_version,
             }
         }
     }
+
+    fn from_error(err: &TallyVmError) -> Self {
+        FfiVmResult {
+            stdout_ptr:  std::ptr::null(),
+            stdout_len:  0,
+            stderr_ptr:  std::ptr::null(),
+            stderr_len:  0,
+            result_ptr:  std::ptr::null(),
+            result_len:  0,
+            exit_info:   FfiExitInfo {
+                exit_message: safe_string_to_cstring(&format!("VM Error: {err}")).into_raw(),
+                exit_code:    err.exit_code(),
+            },
+            gas_used:    0,
+            call_id:     std::ptr::null(),
+            abi_version: 0,
+        }
+    }
+
+    /// Results that never reached the VM still report the call ID of their request
+    fn with_call_id(mut self, call_id: Option<&str>) -> Self {
+        if let (true, Some(call_id)) = (self.call_id.is_null(), call_id) {
+            self.call_id = safe_string_to_cstring(call_id).into_raw();
+        }
+
+        self
+    }
 }
 /// # Safety
 #[no_mangle]
@@ -153,15 +194,40 @@ pub unsafe extern "C" fn free_ffi_vm_result(vm_result: *mut FfiVmResult) {
         mem::drop(result);
     }
 
+    if !(*vm_result).call_id.is_null() {
+        let _ = CString::from_raw((*vm_result).call_id as *mut c_char);
+        (*vm_result).call_id = std::ptr::null();
+    }
+
     free_ffi_exit_info(&mut (*vm_result).exit_info);
 }
 
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub enum FfiWasmEngine {
+    Singlepass,
+    Cranelift,
+}
+
+impl From<FfiWasmEngine> for WasmEngine {
+    fn from(wasm_engine: FfiWasmEngine) -> Self {
+        match wasm_engine {
+            FfiWasmEngine::Singlepass => WasmEngine::Singlepass,
+            FfiWasmEngine::Cranelift => WasmEngine::Cranelift,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct FfiVmSettings {
     pub sedad_home:       *const c_char,
     pub max_result_bytes: usize,
     pub stdout_limit:     usize,
     pub stderr_limit:     usize,
+    /// Cranelift is only meant for off-chain workloads, on-chain execution uses Singlepass
+    pub wasm_engine:      FfiWasmEngine,
+    /// JSON encoded list of gas schedule versions, null only registers the default prices
+    pub gas_schedules:    *const c_char,
 }
 
 pub struct VmSettings {
@@ -169,52 +235,92 @@ pub struct VmSettings {
     pub max_result_bytes: usize,
     pub stdout_limit:     usize,
     pub stderr_limit:     usize,
+    pub wasm_engine:      WasmEngine,
+    pub gas_schedules:    GasScheduleRegistry,
 }
 
 impl FfiVmSettings {
-    unsafe fn into_rust(self) -> VmSettings {
-        VmSettings {
-            sedad_home:       PathBuf::from(CStr::from_ptr(self.sedad_home).to_string_lossy().into_owned()),
+    unsafe fn into_rust(self) -> Result<VmSettings> {
+        let gas_schedules = if self.gas_schedules.is_null() {
+            GasScheduleRegistry::default()
+        } else {
+            GasScheduleRegistry::from_json(CStr::from_ptr(self.gas_schedules).to_bytes()).map_err(RuntimeError::from)?
+        };
+
+        Ok(VmSettings {
+            sedad_home: PathBuf::from(CStr::from_ptr(self.sedad_home).to_string_lossy().into_owned()),
             max_result_bytes: self.max_result_bytes,
-            stdout_limit:     self.stdout_limit,
-            stderr_limit:     self.stderr_limit,
-        }
+            stdout_limit: self.stdout_limit,
+            stderr_limit: self.stderr_limit,
+            wasm_engine: self.wasm_engine.into(),
+            gas_schedules,
+        })
+    }
+}
+
+impl VmSettings {
+    fn gas_schedule(&self, selector: &GasScheduleSelector) -> Result<GasScheduleVersion> {
+        Ok(self.gas_schedules.select(selector).map_err(RuntimeError::from)?.clone())
     }
 }
 
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct FfiTallyRequest {
-    pub wasm_bytes:     *const u8,
-    pub wasm_bytes_len: usize,
-    pub args_ptr:       *const *const c_char,
-    pub args_count:     usize,
-    pub env_keys_ptr:   *const *const c_char,
-    pub env_values_ptr: *const *const c_char,
-    pub env_count:      usize,
+    pub wasm_bytes:           *const u8,
+    pub wasm_bytes_len:       usize,
+    pub args_ptr:             *const *const c_char,
+    pub args_count:           usize,
+    pub env_keys_ptr:         *const *const c_char,
+    pub env_values_ptr:       *const *const c_char,
+    pub env_count:            usize,
+    /// Executions sharing a call ID run one after the other, null runs immediately
+    pub call_id:              *const c_char,
+    /// Name of the gas schedule version to charge, null uses the version active at `block_height`
+    pub gas_schedule_version: *const c_char,
+    pub block_height:         u64,
+    /// Cache ID of an already compiled program, when set `wasm_bytes` is ignored
+    pub wasm_id:              *const c_char,
 }
 
 pub struct TallyRequest {
-    pub wasm_bytes: Vec<u8>,
-    pub args:       Vec<String>,
-    pub envs:       BTreeMap<String, String>,
+    pub wasm_id:      WasmId,
+    pub args:         Vec<String>,
+    pub envs:         BTreeMap<String, String>,
+    pub call_id:      Option<String>,
+    pub gas_schedule: GasScheduleSelector,
+}
+
+/// A null version name selects the version active at `block_height`
+unsafe fn gas_schedule_selector(gas_schedule_version: *const c_char, block_height: u64) -> GasScheduleSelector {
+    if gas_schedule_version.is_null() {
+        GasScheduleSelector::Height(block_height)
+    } else {
+        GasScheduleSelector::Version(CStr::from_ptr(gas_schedule_version).to_string_lossy().into_owned())
+    }
 }
 
 impl FfiTallyRequest {
     unsafe fn into_rust(self) -> TallyRequest {
-        // --- manual copy to avoid SIGBUS on large vector moves ---
-        let len = self.wasm_bytes_len;
-        let mut wasm_bytes = Vec::with_capacity(len);
-        // SAFETY: copying exactly `len` bytes from a valid C buffer
-        unsafe {
-            let src = self.wasm_bytes;
-            let dst = wasm_bytes.as_mut_ptr();
-            std::ptr::copy_nonoverlapping(src, dst, len);
-            wasm_bytes.set_len(len);
-        }
+        let wasm_id = if self.wasm_id.is_null() {
+            // --- manual copy to avoid SIGBUS on large vector moves ---
+            let len = self.wasm_bytes_len;
+            let mut wasm_bytes = Vec::with_capacity(len);
+            // SAFETY: copying exactly `len` bytes from a valid C buffer
+            unsafe {
+                let src = self.wasm_bytes;
+                let dst = wasm_bytes.as_mut_ptr();
+                std::ptr::copy_nonoverlapping(src, dst, len);
+                wasm_bytes.set_len(len);
+            }
+
+            WasmId::Bytes(wasm_bytes)
+        } else {
+            WasmId::Id(CStr::from_ptr(self.wasm_id).to_string_lossy().into_owned())
+        };
 
         TallyRequest {
-            wasm_bytes,
+            wasm_id,
             args: (0..self.args_count)
                 .map(|i| {
                     let ptr = *self.args_ptr.add(i);
@@ -231,40 +337,51 @@ impl FfiTallyRequest {
                     )
                 })
                 .collect(),
+            call_id: self.call_id(),
+            gas_schedule: gas_schedule_selector(self.gas_schedule_version, self.block_height),
         }
     }
+
+    unsafe fn call_id(&self) -> Option<String> {
+        (!self.call_id.is_null()).then(|| CStr::from_ptr(self.call_id).to_string_lossy().into_owned())
+    }
 }
 
 fn convert_vm_result(result: Result<VmResult>, max_result_bytes: usize, is_tally: bool) -> FfiVmResult {
     match result {
         Ok(vm_result) => FfiVmResult::from_result(vm_result, max_result_bytes, is_tally),
-        Err(e) => FfiVmResult {
-            stdout_ptr: std::ptr::null(),
-            stdout_len: 0,
-            stderr_ptr: std::ptr::null(),
-            stderr_len: 0,
-            result_ptr: std::ptr::null(),
-            result_len: 0,
-            exit_info:  FfiExitInfo {
-                exit_message: safe_string_to_cstring(&format!("VM Error: {e}")).into_raw(),
-                exit_code:    e.exit_code(),
-            },
-            gas_used:   0,
-        },
+        Err(e) => FfiVmResult::from_error(&e),
     }
 }
 
+/// Settings are shared by the whole batch, when they are invalid every request fails with the same error
+unsafe fn invalid_settings_results(
+    err: &TallyVmError,
+    request: *const FfiTallyRequest,
+    count: usize,
+) -> *const FfiVmResult {
+    let results: Vec<FfiVmResult> = std::slice::from_raw_parts(request, count)
+        .iter()
+        .map(|request| FfiVmResult::from_error(err).with_call_id(request.call_id().as_deref()))
+        .collect();
+
+    let boxed: Box<[FfiVmResult]> = results.into_boxed_slice();
+    let ptr = boxed.as_ptr();
+    std::mem::forget(boxed);
+    ptr
+}
+
 fn convert_panic_hook_result(result: core::result::Result<FfiVmResult, Box<dyn std::any::Any + Send>>) -> FfiVmResult {
     match result {
         Ok(vm_result) => vm_result,
         Err(e) => FfiVmResult {
-            stdout_ptr: std::ptr::null(),
-            stdout_len: 0,
-            stderr_ptr: std::ptr::null(),
-            stderr_len: 0,
-            result_ptr: std::ptr::null(),
-            result_len: 0,
-            exit_info:  FfiExitInfo {
+            stdout_ptr:  std::ptr::null(),
+            stdout_len:  0,
+            stderr_ptr:  std::ptr::null(),
+            stderr_len:  0,
+            result_ptr:  std::ptr::null(),
+            result_len:  0,
+            exit_info:   FfiExitInfo {
                 exit_message: safe_string_to_cstring(&format!(
                     "The tally VM panicked.\n\
                      Please report this issue at: \
@@ -275,7 +392,9 @@ fn convert_panic_hook_result(result: core::result::Result<FfiVmResult, Box<dyn s
 
                 exit_code: 42,
             },
-            gas_used:   0,
+            gas_used:    0,
+            call_id:     std::ptr::null(),
+            abi_version: 0,
         },
     }
 }
@@ -286,6 +405,7 @@ pub unsafe extern "C" fn execute_tally_request(settings: FfiVmSettings, request:
     let (subscriber, _file_guard) = init_logger(&PathBuf::from(
         CStr::from_ptr(settings.sedad_home).to_string_lossy().into_owned(),
     ));
+    let call_id = request.call_id();
     tracing::subscriber::with_default(subscriber, || {
         let result = std::panic::catch_unwind(|| {
             #[cfg(test)]
@@ -295,7 +415,10 @@ pub unsafe extern "C" fn execute_tally_request(settings: FfiVmSettings, request:
                 }
             }
             let request = request.into_rust();
-            let vm_settings = settings.into_rust();
+            let vm_settings = match settings.into_rust() {
+                Ok(vm_settings) => vm_settings,
+                Err(err) => return FfiVmResult::from_error(&err),
+            };
             let rt = tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
                 .build()
@@ -303,14 +426,21 @@ pub unsafe extern "C" fn execute_tally_request(settings: FfiVmSettings, request:
             let _enter = rt.enter();
             let is_tally = request.envs.get("VM_MODE").is_some_and(|m| m == "tally");
             let res = convert_vm_result(
-                _execute_tally_vm(
-                    &vm_settings.sedad_home,
-                    request.wasm_bytes,
-                    request.args,
-                    request.envs,
-                    vm_settings.stdout_limit,
-                    vm_settings.stderr_limit,
-                ),
+                vm_settings
+                    .gas_schedule(&request.gas_schedule)
+                    .and_then(|gas_schedule| {
+                        _execute_tally_vm(
+                            &vm_settings.sedad_home,
+                            request.wasm_id,
+                            request.args,
+                            request.envs,
+                            vm_settings.stdout_limit,
+                            vm_settings.stderr_limit,
+                            vm_settings.wasm_engine.clone(),
+                            gas_schedule,
+                            request.call_id,
+                        )
+                    }),
                 vm_settings.max_result_bytes,
                 is_tally,
             );
@@ -318,7 +448,7 @@ pub unsafe extern "C" fn execute_tally_request(settings: FfiVmSettings, request:
             drop(rt);
             res
         });
-        convert_panic_hook_result(result)
+        convert_panic_hook_result(result).with_call_id(call_id.as_deref())
     })
 }
 
@@ -329,7 +459,10 @@ pub unsafe extern "C" fn execute_tally_requests(
     request: *const FfiTallyRequest,
     count: usize,
 ) -> *const FfiVmResult {
-    let vm_settings = settings.into_rust();
+    let vm_settings = match settings.into_rust() {
+        Ok(vm_settings) => vm_settings,
+        Err(err) => return invalid_settings_results(&err, request, count),
+    };
     let _file_guard = init_logger(&vm_settings.sedad_home);
 
     // Convert the request pointer to a slice of requests
@@ -340,6 +473,7 @@ pub unsafe extern "C" fn execute_tally_requests(
         let request = raw_request.into_rust();
 
         let is_tally = request.envs.get("VM_MODE").is_some_and(|mode| mode == "tally");
+        let call_id = request.call_id.clone();
         let result = std::panic::catch_unwind(|| {
             #[cfg(test)]
             {
@@ -356,14 +490,21 @@ pub unsafe extern "C" fn execute_tally_requests(
             let _guard = rt.enter();
 
             let res = convert_vm_result(
-                _execute_tally_vm(
-                    &vm_settings.sedad_home,
-                    request.wasm_bytes,
-                    request.args,
-                    request.envs,
-                    vm_settings.stdout_limit,
-                    vm_settings.stderr_limit,
-                ),
+                vm_settings
+                    .gas_schedule(&request.gas_schedule)
+                    .and_then(|gas_schedule| {
+                        _execute_tally_vm(
+                            &vm_settings.sedad_home,
+                            request.wasm_id,
+                            request.args,
+                            request.envs,
+                            vm_settings.stdout_limit,
+                            vm_settings.stderr_limit,
+                            vm_settings.wasm_engine.clone(),
+                            gas_schedule,
+                            request.call_id,
+                        )
+                    }),
                 vm_settings.max_result_bytes,
                 is_tally,
             );
@@ -374,7 +515,7 @@ pub unsafe extern "C" fn execute_tally_requests(
             res
         });
 
-        results.push(convert_panic_hook_result(result));
+        results.push(convert_panic_hook_result(result).with_call_id(call_id.as_deref()));
     }
 
     let boxed: Box<[FfiVmResult]> = results.into_boxed_slice();
@@ -393,7 +534,10 @@ pub unsafe extern "C" fn execute_tally_requests_parallel(
     request: *const FfiTallyRequest,
     count: usize,
 ) -> *const FfiVmResult {
-    let vm_settings = std::sync::Arc::new(settings.into_rust());
+    let vm_settings = match settings.into_rust() {
+        Ok(vm_settings) => std::sync::Arc::new(vm_settings),
+        Err(err) => return invalid_settings_results(&err, request, count),
+    };
     let _file_guard = init_logger(&vm_settings.sedad_home);
     let requests = std::slice::from_raw_parts(request, count).to_vec();
 
@@ -412,21 +556,27 @@ pub unsafe extern "C" fn execute_tally_requests_parallel(
         handles.push(tokio::task::spawn_blocking(move || {
             let req = unsafe { raw.into_rust() };
             let is_tally = req.envs.get("VM_MODE").is_some_and(|m| m == "tally");
+            let call_id = req.call_id.clone();
             let res = std::panic::catch_unwind(|| {
                 convert_vm_result(
-                    _execute_tally_vm(
-                        &vm_settings.sedad_home,
-                        req.wasm_bytes,
-                        req.args,
-                        req.envs,
-                        vm_settings.stdout_limit,
-                        vm_settings.stderr_limit,
-                    ),
+                    vm_settings.gas_schedule(&req.gas_schedule).and_then(|gas_schedule| {
+                        _execute_tally_vm(
+                            &vm_settings.sedad_home,
+                            req.wasm_id,
+                            req.args,
+                            req.envs,
+                            vm_settings.stdout_limit,
+                            vm_settings.stderr_limit,
+                            vm_settings.wasm_engine.clone(),
+                            gas_schedule,
+                            req.call_id,
+                        )
+                    }),
                     vm_settings.max_result_bytes,
                     is_tally,
                 )
             });
-            convert_panic_hook_result(res)
+            convert_panic_hook_result(res).with_call_id(call_id.as_deref())
         }));
     }
 
@@ -448,19 +598,17 @@ pub unsafe extern "C" fn execute_tally_requests_parallel(
 
 const DEFAULT_GAS_LIMIT_ENV_VAR: &str = "DR_TALLY_GAS_LIMIT";
 
-static INSTANCE_MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
-
-fn get_instance_mutex() -> &'static Mutex<()> {
-    INSTANCE_MUTEX.get_or_init(|| Mutex::new(()))
-}
-
+#[allow(clippy::too_many_arguments)]
 fn _execute_tally_vm(
     sedad_home: &Path,
-    wasm_bytes: Vec<u8>,
+    wasm_id: impl Into<WasmId>,
     args: Vec<String>,
     envs: BTreeMap<String, String>,
     stdout_limit: usize,
     stderr_limit: usize,
+    wasm_engine: WasmEngine,
+    gas_schedule: GasScheduleVersion,
+    call_id: Option<String>,
 ) -> Result<VmResult> {
     tracing::info!("Executing Tally VM");
     let env_vars = envs.clone();
@@ -471,8 +619,8 @@ fn _execute_tally_vm(
         )))?;
 
     let call_data = VmCallData {
-        call_id: None,
-        wasm_id: WasmId::Bytes(wasm_bytes),
+        call_id,
+        wasm_id: wasm_id.into(),
         args,
         envs,
         // program_name is not used in the SEDA SDK (It refers in CLI to the first argument)
@@ -480,18 +628,170 @@ fn _execute_tally_vm(
         program_name: "data-request".to_string(),
         start_func: None,
         vm_type: VmType::Tally,
+        wasm_engine,
         gas_limit: Some(gas_limit.parse::<u64>()?),
+        gas_schedule: gas_schedule.gas_schedule,
+        gas_schedule_version: gas_schedule.name,
         ..Default::default()
     };
 
-    let lock = get_instance_mutex().lock().unwrap();
     let runtime_context = RuntimeContext::new(sedad_home, &call_data)?;
-    drop(lock);
     let result = start_runtime(call_data, runtime_context, stdout_limit, stderr_limit);
 
     Ok(result)
 }
 
+fn _compile_wasm_program(
+    sedad_home: &Path,
+    wasm_bytes: Vec<u8>,
+    wasm_engine: WasmEngine,
+    gas_schedule: GasScheduleVersion,
+) -> Result<(String, CompatibilityReport)> {
+    tracing::info!("Compiling WASM program");
+    let call_data = VmCallData {
+        wasm_id: WasmId::Bytes(wasm_bytes),
+        vm_type: VmType::Tally,
+        wasm_engine,
+        gas_schedule: gas_schedule.gas_schedule,
+        gas_schedule_version: gas_schedule.name,
+        ..Default::default()
+    };
+
+    // Goes through the same path as an execution, so the program ends up in the cache
+    let runtime_context = RuntimeContext::new(sedad_home, &call_data)?;
+    let report = check_compatibility(
+        &runtime_context.wasm_module,
+        &call_data.vm_type,
+        call_data.max_memory_pages,
+    );
+
+    Ok((runtime_context.wasm_hash, report))
+}
+
+#[repr(C)]
+pub struct FfiCompileResult {
+    /// Cache ID of the compiled program, null when compiling failed
+    wasm_id:                *const c_char,
+    disallowed_imports_ptr: *const *const c_char,
+    disallowed_imports_len: usize,
+    missing_memory_export:  bool,
+    missing_start_export:   bool,
+    memory_too_large:       bool,
+    /// Null when compiling succeeded
+    error_message:          *const c_char,
+}
+
+impl FfiCompileResult {
+    fn from_error_message(error_message: &str) -> Self {
+        FfiCompileResult {
+            wasm_id:                ptr::null(),
+            disallowed_imports_ptr: ptr::null(),
+            disallowed_imports_len: 0,
+            missing_memory_export:  false,
+            missing_start_export:   false,
+            memory_too_large:       false,
+            error_message:          safe_string_to_cstring(error_message).into_raw(),
+        }
+    }
+
+    fn from_result(result: Result<(String, CompatibilityReport)>) -> Self {
+        let (wasm_id, report) = match result {
+            Ok(compiled) => compiled,
+            Err(err) => return Self::from_error_message(&err.to_string()),
+        };
+
+        let disallowed_imports: Vec<*const c_char> = report
+            .disallowed_imports
+            .iter()
+            .map(|import| safe_string_to_cstring(import).into_raw() as *const _)
+            .collect();
+        let boxed_slice: Box<[*const c_char]> = disallowed_imports.into_boxed_slice();
+        let disallowed_imports_ptr = boxed_slice.as_ptr();
+        let disallowed_imports_len = boxed_slice.len();
+        mem::forget(boxed_slice);
+
+        FfiCompileResult {
+            wasm_id: safe_string_to_cstring(&wasm_id).into_raw(),
+            disallowed_imports_ptr,
+            disallowed_imports_len,
+            missing_memory_export: report.missing_memory_export,
+            missing_start_export: report.missing_start_export,
+            memory_too_large: report.memory_too_large,
+            error_message: ptr::null(),
+        }
+    }
+}
+
+/// # Safety
+#[no_mangle]
+pub unsafe extern "C" fn free_ffi_compile_result(compile_result: *mut FfiCompileResult) {
+    if !(*compile_result).wasm_id.is_null() {
+        let _ = CString::from_raw((*compile_result).wasm_id as *mut c_char);
+        (*compile_result).wasm_id = ptr::null();
+    }
+
+    if !(*compile_result).disallowed_imports_ptr.is_null() {
+        let disallowed_imports = Vec::from_raw_parts(
+            (*compile_result).disallowed_imports_ptr as *mut _,
+            (*compile_result).disallowed_imports_len,
+            (*compile_result).disallowed_imports_len,
+        );
+
+        for elem in disallowed_imports {
+            let s = CString::from_raw(elem);
+            mem::drop(s);
+        }
+        (*compile_result).disallowed_imports_ptr = ptr::null();
+    }
+
+    if !(*compile_result).error_message.is_null() {
+        let _ = CString::from_raw((*compile_result).error_message as *mut c_char);
+        (*compile_result).error_message = ptr::null();
+    }
+}
+
+/// Compiles a program ahead of its first execution and stores it in the WASM cache,
+/// the result reports everything that would keep the program from running.
+/// The gas schedule version is selected like for an execution: by name, or when
+/// `gas_schedule_version` is null by `block_height`. Only executions with the same
+/// version are served from the compiled artifact.
+///
+/// # Safety
+#[no_mangle]
+pub unsafe extern "C" fn compile_wasm_program(
+    settings: FfiVmSettings,
+    wasm_bytes: *const u8,
+    wasm_bytes_len: usize,
+    gas_schedule_version: *const c_char,
+    block_height: u64,
+) -> FfiCompileResult {
+    let result = std::panic::catch_unwind(|| {
+        if wasm_bytes.is_null() || wasm_bytes_len == 0 {
+            return FfiCompileResult::from_result(Err(RuntimeError::NodeError(
+                "No WASM binary was given to compile".to_string(),
+            )
+            .into()));
+        }
+
+        let wasm_bytes = std::slice::from_raw_parts(wasm_bytes, wasm_bytes_len).to_vec();
+        let selector = gas_schedule_selector(gas_schedule_version, block_height);
+
+        FfiCompileResult::from_result(settings.into_rust().and_then(|settings| {
+            let gas_schedule = settings.gas_schedule(&selector)?;
+            _compile_wasm_program(&settings.sedad_home, wasm_bytes, settings.wasm_engine, gas_schedule)
+        }))
+    });
+
+    result.unwrap_or_else(|e| {
+        FfiCompileResult::from_error_message(&format!(
+            "The tally VM panicked while compiling.\n\
+             Please report this issue at: \
+             https://github.com/sedaprotocol/seda-wasm-vm/issues.\n\
+             Panic Error:\n{e:?}"
+        ))
+    })
+}
+
 #[repr(C)]
 pub struct FfiInvalidateWasmCacheInfo {
     wasm_cache_dirs: *const c_char,
@@ -528,19 +828,191 @@ pub unsafe extern "C" fn invalidate_wasm_cache_info(sedad_home_ptr: *const c_cha
     }
 }
 
+#[repr(C)]
+pub struct FfiWasmCachePruneResult {
+    removed_versions: usize,
+    evicted_entries:  usize,
+    cache_size:       u64,
+    /// Null when pruning succeeded
+    error_message:    *const c_char,
+}
+
+/// # Safety
+#[no_mangle]
+pub unsafe extern "C" fn free_ffi_wasm_cache_prune_result(prune_result: *mut FfiWasmCachePruneResult) {
+    if !(*prune_result).error_message.is_null() {
+        let _ = CString::from_raw((*prune_result).error_message as *mut c_char);
+        (*prune_result).error_message = std::ptr::null();
+    }
+}
+
+/// Removes the WASM cache of other library versions and evicts the least recently used
+/// programs until the cache fits in `max_cache_size` bytes, 0 means no size limit.
+///
+/// # Safety
+#[no_mangle]
+pub unsafe extern "C" fn prune_wasm_cache(
+    sedad_home_ptr: *const c_char,
+    max_cache_size: u64,
+) -> FfiWasmCachePruneResult {
+    let sedad_home = PathBuf::from(CStr::from_ptr(sedad_home_ptr).to_string_lossy().into_owned());
+
+    match seda_wasm_vm::wasm_cache::prune_wasm_cache(&sedad_home, max_cache_size) {
+        Ok(report) => FfiWasmCachePruneResult {
+            removed_versions: report.removed_versions,
+            evicted_entries:  report.evicted_entries,
+            cache_size:       report.cache_size,
+            error_message:    std::ptr::null(),
+        },
+        Err(err) => FfiWasmCachePruneResult {
+            removed_versions: 0,
+            evicted_entries:  0,
+            cache_size:       0,
+            error_message:    safe_string_to_cstring(&err.to_string()).into_raw(),
+        },
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{
         collections::BTreeMap,
         ffi::{c_char, CStr, CString},
+        fmt::Write,
         mem,
+        path::{Path, PathBuf},
+        sync::Arc,
     };
 
-    use seda_sdk_rs::bytes::ToBytes;
-    use seda_wasm_vm::vm::{ExitInfo, VmResult};
+    use bn254::{PrivateKey, PublicKey, ECDSA};
+    use ed25519_dalek::{Signer, SigningKey as Ed25519SigningKey};
+    use k256::ecdsa::{Signature, SigningKey};
+    use seda_sdk_rs::{bytes::ToBytes, HttpFetchResponse};
+    use seda_wasm_vm::{
+        abi::AbiVersion,
+        compiling_engine_config,
+        gas_schedule::{GasSchedule, GasScheduleRegistry, GasScheduleSelector, GasScheduleVersion},
+        http_fetcher::MockHttpFetcher,
+        start_runtime,
+        vm::{ExitInfo, VmCallData, VmResult, VmType, WasmEngine, WasmId},
+        wasm_cache::{wasm_cache_id, WasmModuleSize},
+        RuntimeContext,
+        RuntimeError,
+        VmHostError,
+    };
+    use sha2::Sha256;
+    use sha3::{Digest, Keccak256};
     use tempdir::TempDir;
+    use wasmer::wat2wasm;
+
+    use crate::{
+        _compile_wasm_program,
+        _execute_tally_vm,
+        errors::TallyVmError,
+        FfiTallyRequest,
+        FfiVmSettings,
+        FfiWasmEngine,
+        DEFAULT_GAS_LIMIT_ENV_VAR,
+    };
+
+    /// Where the artifact of a program executed with the default memory limit is cached
+    fn artifact_path(
+        sedad_home: &Path,
+        wasm_engine: &WasmEngine,
+        gas_schedule: &GasScheduleVersion,
+        wasm_id: &str,
+    ) -> PathBuf {
+        let engine_config = compiling_engine_config(
+            wasm_engine,
+            VmCallData::default().max_memory_pages,
+            &gas_schedule.name,
+            &gas_schedule.gas_schedule,
+        );
+        seda_wasm_vm::wasm_cache::get_full_wasm_path_from_id(sedad_home, wasm_engine, &engine_config, wasm_id)
+    }
+
+    const I32: &str = "i32";
+    const I64: &str = "i64";
+
+    /// An argument the module built by [`host_call_wasm`] passes to the import
+    enum HostCallArg<'a> {
+        /// Placed in memory and passed as a pointer and a length of the given value type
+        Bytes(&'a [u8], &'static str),
+        /// Passed as is
+        I32(i32),
+    }
+
+    /// Where the module built by [`host_call_wasm`] takes its execution result from
+    enum HostCallOutput {
+        /// The lowest byte of the value returned by the import
+        ReturnValue,
+        /// The call result, the import returns its length
+        CallResult,
+    }
+
+    /// Builds a module whose `_start` calls the `seda_v1` import `import` with `args` and reports `output` through
+    /// `execution_result`.
+    fn host_call_wasm(import: &str, args: &[HostCallArg], output: HostCallOutput) -> Vec<u8> {
+        let mut params = Vec::new();
+        let mut pushes = Vec::new();
+        let mut data = Vec::new();
+        // The inputs are placed from address 16
+        for arg in args {
+            match arg {
+                HostCallArg::Bytes(input, length_type) => {
+                    params.push(format!("i32 {length_type}"));
+                    pushes.push(format!(
+                        "i32.const {} {length_type}.const {}",
+                        16 + data.len(),
+                        input.len()
+                    ));
+                    data.extend_from_slice(input);
+                }
+                HostCallArg::I32(value) => {
+                    params.push("i32".to_string());
+                    pushes.push(format!("i32.const {value}"));
+                }
+            }
+        }
+
+        let output = match output {
+            // Store the lowest byte at address 0 and report that single byte
+            HostCallOutput::ReturnValue => {
+                "(i32.store8 (i32.const 0) (local.get $ret)) (call $execution_result (i32.const 0) (i32.const 1))"
+            }
+            // Write a non-empty call result to address 16 and report it
+            HostCallOutput::CallResult => {
+                "(if (local.get $ret) (then (call $call_result_write (i32.const 16) (local.get $ret))))
+                 (call $execution_result (i32.const 16) (local.get $ret))"
+            }
+        };
 
-    use crate::{FfiTallyRequest, FfiVmSettings, _execute_tally_vm, DEFAULT_GAS_LIMIT_ENV_VAR};
+        let data = data.iter().fold(String::new(), |mut wat, byte| {
+            let _ = write!(wat, "\\{byte:02x}");
+            wat
+        });
+        wat2wasm(
+            format!(
+                r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "seda_v1" "{import}" (func $host (param {params}) (result i32)))
+                (import "seda_v1" "execution_result" (func $execution_result (param i32 i32)))
+                (import "seda_v1" "call_result_write" (func $call_result_write (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "{data}")
+                (func (export "_start") (local $ret i32)
+                    {pushes}
+                    call $host
+                    local.set $ret
+                    {output}))"#,
+                params = params.join(" "),
+                pushes = pushes.join(" "),
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+        .into_owned()
+    }
 
     #[test]
     fn can_get_runtime_versions() {
@@ -574,13 +1046,27 @@ mod test {
             envs.clone(),
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
         let first_run = now.elapsed();
         println!("First run took: {:?}", first_run);
 
         let now = std::time::Instant::now();
-        let _result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let _result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         let second_run = now.elapsed();
         println!("Second run took: {:?}", second_run);
 
@@ -613,6 +1099,9 @@ mod test {
             envs.clone(),
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
         let first_run = now.elapsed();
@@ -620,7 +1109,18 @@ mod test {
 
         seda_wasm_vm::set_test_version_file_name("1.0.1");
         let now = std::time::Instant::now();
-        let _result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let _result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         let second_run = now.elapsed();
         println!("Second run took: {:?}", second_run);
 
@@ -641,108 +1141,698 @@ mod test {
         );
     }
 
+    #[test]
+    fn wasm_cache_id_matches_program_id() {
+        assert_eq!(
+            wasm_cache_id([]),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread")]
-    async fn execute_tally_vm() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+    async fn legacy_cache_entries_are_removed() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
         let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        // VM_MODE dr to force the http_fetch path
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
         envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
 
-        let temp_dir = TempDir::new("execute_tally_vm").unwrap();
+        let temp_dir = TempDir::new("legacy_cache_entries_are_removed").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(
+
+        // A seahash keyed entry and entries from before the cache was split per engine and per engine configuration
+        let cache_dir = seda_wasm_vm::resources_home_dir(tempdir)
+            .join(seda_wasm_vm::wasm_cache::WASM_CACHE_FOLDER_NAME)
+            .join(seda_wasm_vm::get_version_file_name());
+        std::fs::create_dir_all(cache_dir.join("singlepass")).unwrap();
+        let legacy_entry = cache_dir.join("13346297245096443461");
+        std::fs::write(&legacy_entry, b"stale artifact").unwrap();
+        let unpartitioned_entry = cache_dir.join(wasm_cache_id(b"another program"));
+        std::fs::write(&unpartitioned_entry, b"stale artifact").unwrap();
+        let engine_entry = cache_dir.join("singlepass").join(wasm_cache_id(b"another program"));
+        std::fs::write(&engine_entry, b"stale artifact").unwrap();
+
+        _execute_tally_vm(
             tempdir,
             wasm_bytes.to_vec(),
-            vec![hex::encode("testHttpSuccess")],
+            vec![],
             envs,
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
 
-        result.stdout.iter().for_each(|line| print!("{}", line));
-
-        assert_eq!(
-            String::from_utf8_lossy(&result.result.unwrap()),
-            "http_fetch is not allowed in tally".to_string()
-        );
-        assert_eq!(result.gas_used, 19287742795000);
+        assert!(!legacy_entry.exists());
+        assert!(!unpartitioned_entry.exists());
+        assert!(!engine_entry.exists());
+        assert!(artifact_path(
+            tempdir,
+            &WasmEngine::Singlepass,
+            &GasScheduleVersion::default(),
+            &wasm_cache_id(wasm_bytes)
+        )
+        .is_file());
     }
 
-    #[test]
-    fn execute_c_tally_vm() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
-
-        let args = [hex::encode("testHttpSuccess")];
-        let arg_cstrings: Vec<CString> = args
-            .iter()
-            .cloned()
-            .map(|s| CString::new(s).expect("CString::new failed"))
-            .collect();
-        let arg_ptrs: Vec<*const c_char> = arg_cstrings.iter().map(|s| s.as_ptr()).collect();
-
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tampered_cache_entries_are_recompiled() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
         let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        // VM_MODE dr to force the http_fetch path
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
         envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
-        let env_key_cstrings: Vec<CString> = envs
-            .keys()
-            .cloned()
-            .map(|s| CString::new(s).expect("CString::new failed"))
-            .collect();
-        let env_key_ptrs: Vec<*const c_char> = env_key_cstrings.iter().map(|s| s.as_ptr()).collect();
-        let env_value_cstrings: Vec<CString> = envs
-            .values()
-            .cloned()
-            .map(|s| CString::new(s).expect("CString::new failed"))
-            .collect();
-        let env_value_ptrs: Vec<*const c_char> = env_value_cstrings.iter().map(|s| s.as_ptr()).collect();
-
-        let temp_dir = TempDir::new("execute_c_tally_vm").unwrap();
-        let tempdir = temp_dir.path().display().to_string();
-        let tempdir_craw = CString::new(tempdir).unwrap().into_raw();
-
-        let ffi_settings = FfiVmSettings {
-            sedad_home:       tempdir_craw,
-            max_result_bytes: 1024,
-            stdout_limit:     1024,
-            stderr_limit:     1024,
-        };
-
-        let ffi_request = FfiTallyRequest {
-            wasm_bytes:     wasm_bytes.as_ptr(),
-            wasm_bytes_len: wasm_bytes.len(),
-            args_ptr:       arg_ptrs.as_ptr(),
-            args_count:     args.len(),
-            env_keys_ptr:   env_key_ptrs.as_ptr(),
-            env_values_ptr: env_value_ptrs.as_ptr(),
-            env_count:      envs.len(),
-        };
 
-        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
-
-        let result_msg = unsafe {
-            CStr::from_ptr(result.result_ptr as *const c_char)
-                .to_string_lossy()
-                .into_owned()
-        };
+        let temp_dir = TempDir::new("tampered_cache_entries_are_recompiled").unwrap();
+        let tempdir = temp_dir.path();
 
-        assert_eq!(result_msg, "http_fetch is not allowed in tally".to_string());
-        assert_eq!(result.gas_used, 19287742795000);
+        let first = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
-        unsafe {
-            super::free_ffi_vm_result(&mut result);
-            let tempdir_c = CString::from_raw(tempdir_craw);
-            mem::drop(tempdir_c);
+        let artifact_path = artifact_path(
+            tempdir,
+            &WasmEngine::Singlepass,
+            &GasScheduleVersion::default(),
+            &wasm_cache_id(wasm_bytes),
+        );
+        let manifest_path = artifact_path.with_extension("manifest");
+        assert!(manifest_path.is_file());
+
+        // Flip a byte in the artifact, the manifest checksum no longer matches
+        let mut artifact = std::fs::read(&artifact_path).unwrap();
+        let last = artifact.len() - 1;
+        artifact[last] ^= 0xff;
+        std::fs::write(&artifact_path, &artifact).unwrap();
+
+        // Make sure the next execution goes through the disk cache
+        seda_wasm_vm::clear_module_cache(tempdir);
+        let second = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(second.exit_info.exit_code, first.exit_info.exit_code);
+        assert_eq!(second.gas_used, first.gas_used);
+        assert_ne!(std::fs::read(&artifact_path).unwrap(), artifact);
+
+        // An artifact that matches its manifest but fails to deserialize is compiled again
+        let garbage = b"not a serialized module".to_vec();
+        std::fs::write(&artifact_path, &garbage).unwrap();
+        let mut manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        manifest["artifact_hash"] = wasm_cache_id(&garbage).into();
+        std::fs::write(&manifest_path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+        seda_wasm_vm::clear_module_cache(tempdir);
+        let third = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(third.exit_info.exit_code, first.exit_info.exit_code);
+        assert_eq!(third.gas_used, first.gas_used);
+        assert_ne!(std::fs::read(&artifact_path).unwrap(), garbage);
+
+        // An entry without a manifest is not trusted either
+        std::fs::remove_file(&manifest_path).unwrap();
+        seda_wasm_vm::clear_module_cache(tempdir);
+        let wasm_id = WasmId::Id(wasm_cache_id(wasm_bytes));
+        let err = _execute_tally_vm(
+            tempdir,
+            wasm_id,
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            TallyVmError::RuntimeError(RuntimeError::VmHostError(VmHostError::WasmNotCached(_)))
+        ));
+        assert!(!artifact_path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn module_cache_skips_disk() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("module_cache_skips_disk").unwrap();
+        let tempdir = temp_dir.path();
+
+        let first = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        // Once loaded the module is served from memory, even if the disk cache is gone
+        let artifact_path = artifact_path(
+            tempdir,
+            &WasmEngine::Singlepass,
+            &GasScheduleVersion::default(),
+            &wasm_cache_id(wasm_bytes),
+        );
+        std::fs::remove_file(&artifact_path).unwrap();
+        std::fs::remove_file(artifact_path.with_extension("manifest")).unwrap();
+
+        let wasm_id = WasmId::Id(wasm_cache_id(wasm_bytes));
+        let second = _execute_tally_vm(
+            tempdir,
+            wasm_id,
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(second.exit_info.exit_code, first.exit_info.exit_code);
+        assert_eq!(second.gas_used, first.gas_used);
+
+        // A different memory limit is a different module
+        let wasm_id = WasmId::Id(wasm_cache_id(wasm_bytes));
+        let call_data = VmCallData {
+            wasm_id,
+            max_memory_pages: 320,
+            ..Default::default()
+        };
+        let err = RuntimeContext::new(tempdir, &call_data).err().unwrap();
+        assert!(matches!(err, RuntimeError::VmHostError(VmHostError::WasmNotCached(_))));
+
+        // Missing the entry of another configuration leaves it on disk
+        seda_wasm_vm::clear_module_cache(tempdir);
+        _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        let err = RuntimeContext::new(tempdir, &call_data).err().unwrap();
+        assert!(matches!(err, RuntimeError::VmHostError(VmHostError::WasmNotCached(_))));
+        assert!(artifact_path.is_file());
+        assert!(artifact_path.with_extension("manifest").is_file());
+    }
+
+    #[test]
+    fn engine_configs_do_not_overwrite_each_other() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let temp_dir = TempDir::new("engine_configs_do_not_overwrite_each_other").unwrap();
+        let tempdir = temp_dir.path();
+
+        let call_data = |max_memory_pages: u32, gas_schedule_version: &str, wasm_id: WasmId| VmCallData {
+            wasm_id,
+            max_memory_pages,
+            gas_schedule_version: gas_schedule_version.to_string(),
+            ..Default::default()
+        };
+        // Two memory limits, and two version names that share a gas schedule
+        let configs = [(160, "v1"), (320, "v1"), (160, "v1-alias")];
+
+        // Alternating between them compiles every configuration into its own entry
+        for _ in 0..2 {
+            for (max_memory_pages, version) in configs {
+                seda_wasm_vm::clear_module_cache(tempdir);
+                let wasm_id = WasmId::Bytes(wasm_bytes.to_vec());
+                RuntimeContext::new(tempdir, &call_data(max_memory_pages, version, wasm_id)).unwrap();
+            }
+        }
+
+        seda_wasm_vm::clear_module_cache(tempdir);
+        for (max_memory_pages, version) in configs {
+            let wasm_id = WasmId::Id(wasm_cache_id(wasm_bytes));
+            let runtime_context = RuntimeContext::new(tempdir, &call_data(max_memory_pages, version, wasm_id));
+            assert!(runtime_context.is_ok(), "{max_memory_pages} pages, {version}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn module_cache_hits_touch_disk_entry() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("module_cache_hits_touch_disk_entry").unwrap();
+        let tempdir = temp_dir.path();
+
+        let execute = || {
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes.to_vec(),
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+        execute();
+
+        let artifact_path = artifact_path(
+            tempdir,
+            &WasmEngine::Singlepass,
+            &GasScheduleVersion::default(),
+            &wasm_cache_id(wasm_bytes),
+        );
+        let last_week = std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 24 * 60 * 60);
+        std::fs::File::options()
+            .append(true)
+            .open(&artifact_path)
+            .unwrap()
+            .set_modified(last_week)
+            .unwrap();
+
+        // Served from memory, but pruning should still see it as recently used
+        execute();
+        let last_used = std::fs::metadata(&artifact_path).unwrap().modified().unwrap();
+        assert!(last_used > last_week);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn prune_wasm_cache() {
+        let programs: [&'static [u8]; 2] = [
+            include_bytes!("../../test-wasm-files/tally.wasm"),
+            include_bytes!("../../test-wasm-files/simplePriceFeed.wasm"),
+        ];
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("prune_wasm_cache").unwrap();
+        let tempdir = temp_dir.path();
+
+        let mut paths = vec![];
+        for program in programs {
+            _execute_tally_vm(
+                tempdir,
+                program.to_vec(),
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap();
+            paths.push(artifact_path(
+                tempdir,
+                &WasmEngine::Singlepass,
+                &GasScheduleVersion::default(),
+                &wasm_cache_id(program),
+            ));
         }
+
+        // The tally program was used longest ago
+        let last_week = std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 24 * 60 * 60);
+        std::fs::File::options()
+            .append(true)
+            .open(&paths[0])
+            .unwrap()
+            .set_modified(last_week)
+            .unwrap();
+
+        let old_version_dir = paths[0]
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .with_file_name("0.0.1");
+        std::fs::create_dir_all(&old_version_dir).unwrap();
+        std::fs::write(old_version_dir.join("leftover"), b"compiled").unwrap();
+
+        let entry_size = |path: &std::path::Path| {
+            std::fs::metadata(path).unwrap().len() + std::fs::metadata(path.with_extension("manifest")).unwrap().len()
+        };
+        let kept_size = entry_size(&paths[1]);
+
+        let sedad_home = CString::new(tempdir.to_str().unwrap()).unwrap();
+        let mut result = unsafe { super::prune_wasm_cache(sedad_home.as_ptr(), kept_size) };
+        assert!(result.error_message.is_null());
+        assert_eq!(result.removed_versions, 1);
+        assert_eq!(result.evicted_entries, 1);
+        assert_eq!(result.cache_size, kept_size);
+        unsafe { super::free_ffi_wasm_cache_prune_result(&mut result) };
+
+        assert!(!old_version_dir.exists());
+        assert!(!paths[0].exists());
+        assert!(!paths[0].with_extension("manifest").exists());
+        assert!(paths[1].is_file());
     }
 
     #[test]
-    fn execute_c_tally_vm_exceeds_byte_limit() {
+    fn compile_wasm_program() {
         let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let temp_dir = TempDir::new("compile_wasm_program").unwrap();
+        let tempdir = temp_dir.path();
 
-        let args = [hex::encode("tally"), hex::encode("[{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"200\",\"reveal\":[2]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"201\",\"reveal\":[5]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"202\",\"reveal\":[6]}]"), hex::encode("[0,0,0]")];
+        let sedad_home = CString::new(tempdir.to_str().unwrap()).unwrap();
+        let settings = || FfiVmSettings {
+            sedad_home:       sedad_home.as_ptr(),
+            max_result_bytes: 1024,
+            stdout_limit:     1024,
+            stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
+        };
+
+        let mut result = unsafe {
+            super::compile_wasm_program(settings(), wasm_bytes.as_ptr(), wasm_bytes.len(), std::ptr::null(), 0)
+        };
+        assert!(result.error_message.is_null());
+        assert_eq!(result.disallowed_imports_len, 0);
+        assert!(!result.missing_memory_export);
+        assert!(!result.missing_start_export);
+        assert!(!result.memory_too_large);
+
+        let wasm_id = unsafe { CStr::from_ptr(result.wasm_id) }.to_str().unwrap().to_string();
+        assert_eq!(wasm_id, wasm_cache_id(wasm_bytes));
+        unsafe { super::free_ffi_compile_result(&mut result) };
+
+        // The program runs straight from the cache through the default FFI path, which
+        // selects the gas schedule version by block height like the compilation did
+        let env_keys = [CString::new(DEFAULT_GAS_LIMIT_ENV_VAR).unwrap()];
+        let env_values = [CString::new("150000000000000").unwrap()];
+        let env_key_ptrs: Vec<*const c_char> = env_keys.iter().map(|s| s.as_ptr()).collect();
+        let env_value_ptrs: Vec<*const c_char> = env_values.iter().map(|s| s.as_ptr()).collect();
+        let wasm_id = CString::new(wasm_id).unwrap();
+        let ffi_request = FfiTallyRequest {
+            wasm_id:              wasm_id.as_ptr(),
+            wasm_bytes:           std::ptr::null(),
+            wasm_bytes_len:       0,
+            args_ptr:             std::ptr::null(),
+            args_count:           0,
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            env_keys.len(),
+            call_id:              std::ptr::null(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
+        };
+
+        let mut result = unsafe { super::execute_tally_request(settings(), ffi_request) };
+        assert_eq!(result.exit_info.exit_code, 255);
+        unsafe { super::free_ffi_vm_result(&mut result) };
+    }
+
+    #[test]
+    fn compile_wasm_program_without_bytes() {
+        let temp_dir = TempDir::new("compile_wasm_program_without_bytes").unwrap();
+        let sedad_home = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let settings = || FfiVmSettings {
+            sedad_home:       sedad_home.as_ptr(),
+            max_result_bytes: 1024,
+            stdout_limit:     1024,
+            stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
+        };
+
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        for (wasm_bytes_ptr, wasm_bytes_len) in [(std::ptr::null(), 1024), (wasm_bytes.as_ptr(), 0)] {
+            let mut result =
+                unsafe { super::compile_wasm_program(settings(), wasm_bytes_ptr, wasm_bytes_len, std::ptr::null(), 0) };
+            assert!(result.wasm_id.is_null());
+            let error_message = unsafe { CStr::from_ptr(result.error_message) }.to_str().unwrap();
+            assert!(error_message.contains("No WASM binary was given to compile"));
+            unsafe { super::free_ffi_compile_result(&mut result) };
+        }
+    }
+
+    #[test]
+    fn compile_wasm_program_reports_incompatibilities() {
+        let temp_dir = TempDir::new("compile_wasm_program_reports_incompatibilities").unwrap();
+        let tempdir = temp_dir.path();
+
+        let wasm_bytes = include_bytes!("../../test-wasm-files/assign_too_much_memory.wasm");
+        let (_, report) = _compile_wasm_program(
+            tempdir,
+            wasm_bytes.to_vec(),
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+        )
+        .unwrap();
+        assert!(report.memory_too_large);
+        assert!(report.disallowed_imports.is_empty());
+
+        let wasm_bytes = wat2wasm(br#"(module (import "seda_v1" "db_get" (func)) (import "env" "abort" (func)))"#)
+            .unwrap()
+            .into_owned();
+        let (_, report) = _compile_wasm_program(
+            tempdir,
+            wasm_bytes,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+        )
+        .unwrap();
+        assert_eq!(report.disallowed_imports, vec!["seda_v1::db_get", "env::abort"]);
+        assert!(report.missing_memory_export);
+        assert!(report.missing_start_export);
+        assert!(!report.memory_too_large);
+
+        let err = _compile_wasm_program(
+            tempdir,
+            b"not a wasm binary".to_vec(),
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, TallyVmError::RuntimeError(_)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_cache_writes() {
+        let programs: [&'static [u8]; 2] = [
+            include_bytes!("../../test-wasm-files/tally.wasm"),
+            include_bytes!("../../test-wasm-files/simplePriceFeed.wasm"),
+        ];
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("concurrent_cache_writes").unwrap();
+        let tempdir = temp_dir.path().to_path_buf();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let tempdir = tempdir.clone();
+                let envs = envs.clone();
+                tokio::task::spawn_blocking(move || {
+                    _execute_tally_vm(
+                        &tempdir,
+                        programs[i % 2].to_vec(),
+                        vec![],
+                        envs,
+                        1024,
+                        1024,
+                        WasmEngine::Singlepass,
+                        GasScheduleVersion::default(),
+                        None,
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // Every program is compiled once and no temporary files are left behind
+        let artifact = artifact_path(
+            &tempdir,
+            &WasmEngine::Singlepass,
+            &GasScheduleVersion::default(),
+            &wasm_cache_id(programs[0]),
+        );
+        let mut entries: Vec<String> = std::fs::read_dir(artifact.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+
+        let mut expected: Vec<String> = programs
+            .iter()
+            .flat_map(|program| {
+                let id = wasm_cache_id(program);
+                [id.clone(), format!("{id}.manifest")]
+            })
+            .collect();
+        expected.sort();
+
+        assert_eq!(entries, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_from_cache_id() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_from_cache_id").unwrap();
+        let tempdir = temp_dir.path();
+
+        // The first run compiles the binary and stores it in the cache
+        let from_bytes = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        let wasm_id = WasmId::Id(wasm_cache_id(wasm_bytes));
+        let from_id = _execute_tally_vm(
+            tempdir,
+            wasm_id,
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(from_id.exit_info.exit_code, from_bytes.exit_info.exit_code);
+        assert_eq!(from_id.result, from_bytes.result);
+        assert_eq!(from_id.gas_used, from_bytes.gas_used);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_from_uncached_id() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_from_uncached_id").unwrap();
+        let tempdir = temp_dir.path();
+
+        let wasm_id = WasmId::Id(wasm_cache_id(b"not a cached program"));
+        let err = _execute_tally_vm(
+            tempdir,
+            wasm_id,
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            TallyVmError::RuntimeError(RuntimeError::VmHostError(VmHostError::WasmNotCached(_)))
+        ));
+
+        // IDs end up in a path, so anything that could escape the cache directory is rejected
+        let wasm_id = WasmId::Id("../../escape".to_string());
+        let err = _execute_tally_vm(
+            tempdir,
+            wasm_id,
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            TallyVmError::RuntimeError(RuntimeError::VmHostError(VmHostError::InvalidWasmCacheId(_)))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        // VM_MODE dr to force the http_fetch path
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![hex::encode("testHttpSuccess")],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        result.stdout.iter().for_each(|line| print!("{}", line));
+
+        assert_eq!(
+            String::from_utf8_lossy(&result.result.unwrap()),
+            "http_fetch is not allowed in tally".to_string()
+        );
+        assert_eq!(result.gas_used, 19287742795000);
+        assert_eq!(result.abi_version, Some(AbiVersion::V1));
+    }
+
+    #[test]
+    fn execute_c_tally_vm() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+
+        let args = [hex::encode("testHttpSuccess")];
         let arg_cstrings: Vec<CString> = args
             .iter()
             .cloned()
@@ -751,9 +1841,9 @@ mod test {
         let arg_ptrs: Vec<*const c_char> = arg_cstrings.iter().map(|s| s.as_ptr()).collect();
 
         let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert("VM_MODE".to_string(), "tally".to_string());
+        // VM_MODE dr to force the http_fetch path
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
         envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
-        envs.insert("CONSENSUS".to_string(), true.to_string());
         let env_key_cstrings: Vec<CString> = envs
             .keys()
             .cloned()
@@ -767,25 +1857,220 @@ mod test {
             .collect();
         let env_value_ptrs: Vec<*const c_char> = env_value_cstrings.iter().map(|s| s.as_ptr()).collect();
 
-        let temp_dir = TempDir::new("execute_c_tally_vm_exceeds_byte_limit").unwrap();
+        let temp_dir = TempDir::new("execute_c_tally_vm").unwrap();
         let tempdir = temp_dir.path().display().to_string();
         let tempdir_craw = CString::new(tempdir).unwrap().into_raw();
 
         let ffi_settings = FfiVmSettings {
             sedad_home:       tempdir_craw,
-            max_result_bytes: 1, // Set to 1 byte to force the error
+            max_result_bytes: 1024,
+            stdout_limit:     1024,
+            stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
+        };
+
+        let ffi_request = FfiTallyRequest {
+            wasm_id:              std::ptr::null(),
+            wasm_bytes:           wasm_bytes.as_ptr(),
+            wasm_bytes_len:       wasm_bytes.len(),
+            args_ptr:             arg_ptrs.as_ptr(),
+            args_count:           args.len(),
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            envs.len(),
+            call_id:              std::ptr::null(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
+        };
+
+        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
+
+        let result_msg = unsafe {
+            CStr::from_ptr(result.result_ptr as *const c_char)
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        assert_eq!(result_msg, "http_fetch is not allowed in tally".to_string());
+        assert_eq!(result.gas_used, 19287742795000);
+
+        unsafe {
+            super::free_ffi_vm_result(&mut result);
+            let tempdir_c = CString::from_raw(tempdir_craw);
+            mem::drop(tempdir_c);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn results_carry_call_id() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("results_carry_call_id").unwrap();
+        let tempdir = temp_dir.path().to_path_buf();
+
+        // Executions sharing an ID are queued, all of them still complete
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let tempdir = tempdir.clone();
+                let envs = envs.clone();
+                tokio::task::spawn_blocking(move || {
+                    let call_id = format!("call-{}", i % 2);
+                    let result = _execute_tally_vm(
+                        &tempdir,
+                        wasm_bytes.to_vec(),
+                        vec![],
+                        envs,
+                        1024,
+                        1024,
+                        WasmEngine::Singlepass,
+                        GasScheduleVersion::default(),
+                        Some(call_id.clone()),
+                    )
+                    .unwrap();
+                    (call_id, result)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let (call_id, result) = handle.await.unwrap();
+            assert_eq!(result.call_id, Some(call_id));
+        }
+
+        // Without an ID executions run under generated keys that are not reported back
+        let first = _execute_tally_vm(
+            &tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        let second = _execute_tally_vm(
+            &tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(first.call_id, None);
+        assert_eq!(second.call_id, None);
+    }
+
+    #[test]
+    fn ffi_results_carry_call_id() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("ffi_results_carry_call_id").unwrap();
+        let tempdir = temp_dir.path();
+
+        // Requests that fail before reaching the VM are still tagged
+        let env_keys: Vec<CString> = envs.keys().map(|key| CString::new(key.as_str()).unwrap()).collect();
+        let env_key_ptrs: Vec<*const c_char> = env_keys.iter().map(|key| key.as_ptr()).collect();
+        let env_values: Vec<CString> = envs
+            .values()
+            .map(|value| CString::new(value.as_str()).unwrap())
+            .collect();
+        let env_value_ptrs: Vec<*const c_char> = env_values.iter().map(|value| value.as_ptr()).collect();
+        let sedad_home = CString::new(tempdir.to_str().unwrap()).unwrap();
+        let wasm_id = CString::new("not-a-cache-id").unwrap();
+        let call_id = CString::new("request-1").unwrap();
+
+        let ffi_settings = FfiVmSettings {
+            sedad_home:       sedad_home.as_ptr(),
+            max_result_bytes: 1024,
+            stdout_limit:     1024,
+            stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
+        };
+        let ffi_request = FfiTallyRequest {
+            wasm_id:              wasm_id.as_ptr(),
+            wasm_bytes:           std::ptr::null(),
+            wasm_bytes_len:       0,
+            args_ptr:             std::ptr::null(),
+            args_count:           0,
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            envs.len(),
+            call_id:              call_id.as_ptr(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
+        };
+
+        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
+        assert_ne!(result.exit_info.exit_code, 0);
+        assert_eq!(unsafe { CStr::from_ptr(result.call_id) }, call_id.as_c_str());
+        unsafe { super::free_ffi_vm_result(&mut result) };
+    }
+
+    #[test]
+    fn execute_c_tally_vm_exceeds_byte_limit() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+
+        let args = [hex::encode("tally"), hex::encode("[{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"200\",\"reveal\":[2]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"201\",\"reveal\":[5]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"202\",\"reveal\":[6]}]"), hex::encode("[0,0,0]")];
+        let arg_cstrings: Vec<CString> = args
+            .iter()
+            .cloned()
+            .map(|s| CString::new(s).expect("CString::new failed"))
+            .collect();
+        let arg_ptrs: Vec<*const c_char> = arg_cstrings.iter().map(|s| s.as_ptr()).collect();
+
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        envs.insert("CONSENSUS".to_string(), true.to_string());
+        let env_key_cstrings: Vec<CString> = envs
+            .keys()
+            .cloned()
+            .map(|s| CString::new(s).expect("CString::new failed"))
+            .collect();
+        let env_key_ptrs: Vec<*const c_char> = env_key_cstrings.iter().map(|s| s.as_ptr()).collect();
+        let env_value_cstrings: Vec<CString> = envs
+            .values()
+            .cloned()
+            .map(|s| CString::new(s).expect("CString::new failed"))
+            .collect();
+        let env_value_ptrs: Vec<*const c_char> = env_value_cstrings.iter().map(|s| s.as_ptr()).collect();
+
+        let temp_dir = TempDir::new("execute_c_tally_vm_exceeds_byte_limit").unwrap();
+        let tempdir = temp_dir.path().display().to_string();
+        let tempdir_craw = CString::new(tempdir).unwrap().into_raw();
+
+        let ffi_settings = FfiVmSettings {
+            sedad_home:       tempdir_craw,
+            max_result_bytes: 1, // Set to 1 byte to force the error
             stdout_limit:     1024,
             stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
         };
 
         let ffi_request = FfiTallyRequest {
-            wasm_bytes:     wasm_bytes.as_ptr(),
-            wasm_bytes_len: wasm_bytes.len(),
-            args_ptr:       arg_ptrs.as_ptr(),
-            args_count:     args.len(),
-            env_keys_ptr:   env_key_ptrs.as_ptr(),
-            env_values_ptr: env_value_ptrs.as_ptr(),
-            env_count:      envs.len(),
+            wasm_id:              std::ptr::null(),
+            wasm_bytes:           wasm_bytes.as_ptr(),
+            wasm_bytes_len:       wasm_bytes.len(),
+            args_ptr:             arg_ptrs.as_ptr(),
+            args_count:           args.len(),
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            envs.len(),
+            call_id:              std::ptr::null(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
         };
 
         let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
@@ -844,142 +2129,1485 @@ mod test {
             max_result_bytes: 1,
             stdout_limit:     1024,
             stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
+        };
+
+        let ffi_request = FfiTallyRequest {
+            wasm_id:              std::ptr::null(),
+            wasm_bytes:           wasm_bytes.as_ptr(),
+            wasm_bytes_len:       wasm_bytes.len(),
+            args_ptr:             arg_ptrs.as_ptr(),
+            args_count:           args.len(),
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            envs.len(),
+            call_id:              std::ptr::null(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
+        };
+
+        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
+
+        let exit_msg = unsafe {
+            CStr::from_ptr(result.exit_info.exit_message)
+                .to_string_lossy()
+                .into_owned()
+        };
+        assert_eq!(exit_msg, "Ok".to_string());
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.gas_used, 9156653346250);
+
+        unsafe {
+            super::free_ffi_vm_result(&mut result);
+            let tempdir_c = CString::from_raw(tempdir_craw);
+            mem::drop(tempdir_c);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_proxy_http_fetch() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_proxy_http_fetch").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![hex::encode("testProxyHttpFetch")],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        result.stdout.iter().for_each(|line| print!("{}", line));
+
+        assert_eq!(
+            String::from_utf8_lossy(&result.result.unwrap()),
+            "proxy_http_fetch is not allowed in tally".to_string()
+        );
+        assert_eq!(result.gas_used, 21736902545000);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_data_request_vm_with_mock_fetcher() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+
+        let temp_dir = TempDir::new("execute_data_request_vm_with_mock_fetcher").unwrap();
+        let tempdir = temp_dir.path();
+
+        let call_data = VmCallData {
+            wasm_id: WasmId::Bytes(wasm_bytes.to_vec()),
+            args: vec![hex::encode("testHttpSuccess")],
+            envs,
+            vm_type: VmType::DataRequest,
+            gas_limit: Some(150_000_000_000_000),
+            ..Default::default()
+        };
+
+        let body = "{\"userId\":1,\"id\":1,\"title\":\"delectus aut autem\",\"completed\":false}";
+        let fetcher = Arc::new(MockHttpFetcher::new(HttpFetchResponse {
+            url:            "https://jsonplaceholder.typicode.com/todos/1".to_string(),
+            status:         200,
+            headers:        Default::default(),
+            content_length: body.len(),
+            bytes:          body.as_bytes().to_vec(),
+        }));
+
+        let runtime_context = RuntimeContext::new(tempdir, &call_data)
+            .unwrap()
+            .with_http_fetcher(fetcher.clone());
+        let result = start_runtime(call_data, runtime_context, 1024, 1024);
+
+        assert_eq!(result.exit_info.exit_code, 0);
+        // The program parses the fetched todo and returns its fields
+        assert_eq!(
+            String::from_utf8_lossy(&result.result.unwrap()),
+            "1:1:delectus aut autem:false"
+        );
+        assert_eq!(fetcher.http_calls().len(), 1);
+        assert!(fetcher.proxy_calls().is_empty());
+        assert!(result.gas_used > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn data_request_vm_requires_http_fetcher() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+
+        let temp_dir = TempDir::new("data_request_vm_requires_http_fetcher").unwrap();
+        let tempdir = temp_dir.path();
+
+        let call_data = VmCallData {
+            wasm_id: WasmId::Bytes(wasm_bytes.to_vec()),
+            args: vec![hex::encode("testHttpSuccess")],
+            vm_type: VmType::DataRequest,
+            gas_limit: Some(150_000_000_000_000),
+            ..Default::default()
+        };
+
+        let runtime_context = RuntimeContext::new(tempdir, &call_data).unwrap();
+        let result = start_runtime(call_data, runtime_context, 1024, 1024);
+
+        assert_eq!(result.exit_info.exit_code, 3);
+        assert!(result.result.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_core_vm() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+
+        let temp_dir = TempDir::new("execute_core_vm").unwrap();
+        let tempdir = temp_dir.path();
+
+        let call_data = VmCallData {
+            wasm_id: WasmId::Bytes(wasm_bytes.to_vec()),
+            args: vec![
+                hex::encode("tally"),
+                hex::encode("[{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"200\",\"reveal\":[2]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"201\",\"reveal\":[5]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"202\",\"reveal\":[6]}]"),
+                hex::encode("[0,0,0]"),
+            ],
+            envs: BTreeMap::from([
+                ("VM_MODE".to_string(), "tally".to_string()),
+                ("CONSENSUS".to_string(), "true".to_string()),
+            ]),
+            vm_type: VmType::Core,
+            gas_limit: Some(150_000_000_000_000),
+            ..Default::default()
+        };
+
+        let runtime_context = RuntimeContext::new(tempdir, &call_data).unwrap();
+        let result = start_runtime(call_data, runtime_context, 1024, 1024);
+
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert!(!result.result.unwrap().is_empty());
+        assert!(result.gas_used > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_with_cranelift() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+        envs.insert("CONSENSUS".to_string(), "true".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        let args = vec![
+            hex::encode("tally"),
+            hex::encode("[{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"200\",\"reveal\":[2]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"201\",\"reveal\":[5]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"202\",\"reveal\":[6]}]"),
+            hex::encode("[0,0,0]"),
+        ];
+
+        let temp_dir = TempDir::new("execute_tally_vm_with_cranelift").unwrap();
+        let tempdir = temp_dir.path();
+
+        let singlepass = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            args.clone(),
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        let cranelift = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            args,
+            envs,
+            1024,
+            1024,
+            WasmEngine::Cranelift,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        // Metering does not depend on the engine
+        assert_eq!(cranelift.exit_info.exit_code, 0);
+        assert_eq!(cranelift.result, singlepass.result);
+        assert_eq!(cranelift.gas_used, singlepass.gas_used);
+
+        // Each engine has its own cache entry
+        let wasm_id = wasm_cache_id(wasm_bytes);
+        for wasm_engine in [WasmEngine::Singlepass, WasmEngine::Cranelift] {
+            let artifact_path = artifact_path(tempdir, &wasm_engine, &GasScheduleVersion::default(), &wasm_id);
+            assert!(artifact_path.is_file());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_with_gas_schedule() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("CONSENSUS".to_string(), "true".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+
+        let args = vec![
+            hex::encode("tally"),
+            hex::encode("[{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"200\",\"reveal\":[2]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"201\",\"reveal\":[5]},{\"dr_block_height\":1,\"exit_code\":0,\"gas_used\":\"202\",\"reveal\":[6]}]"),
+            hex::encode("[0,0,0]"),
+        ];
+
+        let gas_schedules = GasScheduleRegistry::new(vec![
+            GasScheduleVersion::default(),
+            GasScheduleVersion {
+                name:              "v2".to_string(),
+                activation_height: 100,
+                gas_schedule:      GasSchedule {
+                    startup: GasSchedule::default().startup + 1_000_000_000_000,
+                    ..Default::default()
+                },
+            },
+            GasScheduleVersion {
+                name:              "v3".to_string(),
+                activation_height: 200,
+                gas_schedule:      GasSchedule {
+                    per_operation: GasSchedule::default().per_operation * 2,
+                    ..Default::default()
+                },
+            },
+        ])
+        .unwrap();
+
+        let temp_dir = TempDir::new("execute_tally_vm_with_gas_schedule").unwrap();
+        let tempdir = temp_dir.path();
+        let execute = |selector: GasScheduleSelector| {
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes.to_vec(),
+                args.clone(),
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                gas_schedules.select(&selector).unwrap().clone(),
+                None,
+            )
+            .unwrap()
         };
 
-        let ffi_request = FfiTallyRequest {
-            wasm_bytes:     wasm_bytes.as_ptr(),
-            wasm_bytes_len: wasm_bytes.len(),
-            args_ptr:       arg_ptrs.as_ptr(),
-            args_count:     args.len(),
-            env_keys_ptr:   env_key_ptrs.as_ptr(),
-            env_values_ptr: env_value_ptrs.as_ptr(),
-            env_count:      envs.len(),
+        let v1 = execute(GasScheduleSelector::Height(99));
+        assert_eq!(v1.exit_info.exit_code, 0);
+
+        // Host side prices apply right away
+        let v2 = execute(GasScheduleSelector::Height(100));
+        assert_eq!(v2.gas_used, v1.gas_used + 1_000_000_000_000);
+        assert_eq!(
+            execute(GasScheduleSelector::Version("v2".to_string())).gas_used,
+            v2.gas_used
+        );
+
+        // Operator prices are compiled in, every version gets its own compiled module
+        let v3 = execute(GasScheduleSelector::Height(u64::MAX));
+        assert_eq!(v3.result, v1.result);
+        assert!(v3.gas_used > v1.gas_used);
+        assert_eq!(execute(GasScheduleSelector::Height(0)).gas_used, v1.gas_used);
+
+        // The compiled modules do not overwrite each other on disk
+        let wasm_id = wasm_cache_id(wasm_bytes);
+        for version in gas_schedules.versions() {
+            let artifact_path = artifact_path(tempdir, &WasmEngine::Singlepass, version, &wasm_id);
+            assert!(artifact_path.is_file(), "{}", version.name);
+        }
+    }
+
+    #[test]
+    fn gas_schedule_registry() {
+        let version = |name: &str, activation_height: u64| GasScheduleVersion {
+            name: name.to_string(),
+            activation_height,
+            gas_schedule: GasSchedule::default(),
+        };
+
+        let gas_schedules = GasScheduleRegistry::new(vec![version("v2", 100), version("v1", 0)]).unwrap();
+        assert_eq!(gas_schedules.versions(), &[version("v1", 0), version("v2", 100)]);
+        assert_eq!(gas_schedules.latest().name, "v2");
+
+        let select = |selector| gas_schedules.select(&selector).map(|version| version.name.clone());
+        assert_eq!(select(GasScheduleSelector::Height(0)).unwrap(), "v1");
+        assert_eq!(select(GasScheduleSelector::Height(99)).unwrap(), "v1");
+        assert_eq!(select(GasScheduleSelector::Height(100)).unwrap(), "v2");
+        assert_eq!(select(GasScheduleSelector::Version("v1".to_string())).unwrap(), "v1");
+        assert!(matches!(
+            select(GasScheduleSelector::Version("v3".to_string())),
+            Err(VmHostError::UnknownGasScheduleVersion(_))
+        ));
+
+        // Every height has to resolve to exactly one version
+        for versions in [
+            vec![],
+            vec![version("v1", 1)],
+            vec![version("v1", 0), version("v1", 100)],
+            vec![version("v1", 0), version("v2", 0)],
+        ] {
+            assert!(matches!(
+                GasScheduleRegistry::new(versions),
+                Err(VmHostError::InvalidGasScheduleRegistry(_))
+            ));
+        }
+
+        let json = serde_json::to_vec(gas_schedules.versions()).unwrap();
+        assert_eq!(GasScheduleRegistry::from_json(&json).unwrap(), gas_schedules);
+
+        // Without a configured registry every height keeps the default prices, v2 is opt-in
+        let built_in = GasScheduleRegistry::default();
+        assert_eq!(built_in.versions(), &[GasScheduleVersion::default()]);
+        assert_eq!(
+            built_in.select(&GasScheduleSelector::Height(1_000_000)).unwrap(),
+            &GasScheduleVersion::default()
+        );
+    }
+
+    #[test]
+    fn gas_schedules_are_frozen() {
+        // Registered versions price blocks that are replayed later, changed prices need a new version
+        assert_eq!(
+            GasSchedule::default().hash(),
+            "9c48cd9a3bcbd13eed86daa4caff7d5dfe232972cfe79cd3e5bc788e77d7fedd"
+        );
+        assert_eq!(
+            GasSchedule::v2().hash(),
+            "6ff76dd343d8e3d04650d63372c3fe501bc6c8b4524a39a60e2a7918a5442dbd"
+        );
+    }
+
+    /// Builds a module with one page of memory whose `_start` runs the WAT `instructions`.
+    /// Like every program it imports WASI, the VM picks the WASI version from the imports.
+    fn start_wasm(instructions: &str) -> Vec<u8> {
+        wat2wasm(
+            format!(
+                r#"(module
+                    (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                    (memory (export "memory") 1)
+                    (func (export "_start") {instructions}))"#
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+        .into_owned()
+    }
+
+    /// Builds a module whose `_start` grows its memory by `pages`
+    fn memory_grow_wasm(pages: u32) -> Vec<u8> {
+        start_wasm(&format!("(drop (memory.grow (i32.const {pages})))"))
+    }
+
+    /// Builds a module with a table of 64 functions and a passive segment of 32 functions, whose `_start` runs the WAT
+    /// `instructions`
+    fn table_wasm(instructions: &str) -> Vec<u8> {
+        let elements = "$f ".repeat(32);
+        wat2wasm(
+            format!(
+                r#"(module
+                    (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                    (memory (export "memory") 1)
+                    (table 64 funcref)
+                    (func $f)
+                    (elem func {elements})
+                    (func (export "_start") {instructions}))"#
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+        .into_owned()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_charges_memory_grow_per_page() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+
+        let per_page = GasScheduleVersion {
+            name:              "v2".to_string(),
+            activation_height: 100,
+            gas_schedule:      GasSchedule {
+                memory_grow_per_page: 1_000_000_000_000,
+                ..Default::default()
+            },
+        };
+
+        let temp_dir = TempDir::new("execute_tally_vm_charges_memory_grow_per_page").unwrap();
+        let tempdir = temp_dir.path();
+        let execute = |pages: u32, gas_schedule: &GasScheduleVersion| {
+            _execute_tally_vm(
+                tempdir,
+                memory_grow_wasm(pages),
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                gas_schedule.clone(),
+                None,
+            )
+            .unwrap()
+        };
+
+        // Without a per page price the page count makes no difference
+        let default = GasScheduleVersion::default();
+        assert_eq!(execute(1, &default).gas_used, execute(3, &default).gas_used);
+
+        // The built-in v2 prices every page
+        let v2 = GasScheduleVersion::v2(1);
+        assert_eq!(
+            execute(3, &v2).gas_used,
+            execute(1, &v2).gas_used + 2 * v2.gas_schedule.memory_grow_per_page
+        );
+
+        let one_page = execute(1, &per_page);
+        let three_pages = execute(3, &per_page);
+        assert_eq!(one_page.exit_info.exit_code, 0);
+        assert_eq!(three_pages.exit_info.exit_code, 0);
+        assert_eq!(three_pages.gas_used, one_page.gas_used + 2_000_000_000_000);
+
+        // Requesting more pages than the gas limit pays for runs out of gas before the memory grows
+        let too_many_pages = execute(
+            60,
+            &GasScheduleVersion {
+                gas_schedule: GasSchedule {
+                    memory_grow_per_page: 10_000_000_000_000,
+                    ..Default::default()
+                },
+                ..per_page.clone()
+            },
+        );
+        assert_eq!(too_many_pages.exit_info.exit_code, 250);
+        assert_eq!(too_many_pages.gas_used, 150_000_000_000_000);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_charges_bulk_memory_per_byte() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+
+        let per_byte = GasScheduleVersion {
+            name:              "v2".to_string(),
+            activation_height: 100,
+            gas_schedule:      GasSchedule {
+                memory_bulk_per_byte: 1_000_000_000_000,
+                ..Default::default()
+            },
+        };
+
+        let memory_fill = |bytes: u32| {
+            start_wasm(&format!(
+                "(memory.fill (i32.const 0) (i32.const 0) (i32.const {bytes}))"
+            ))
+        };
+        let memory_copy = |bytes: u32| {
+            start_wasm(&format!(
+                "(memory.copy (i32.const 32) (i32.const 0) (i32.const {bytes}))"
+            ))
+        };
+
+        let temp_dir = TempDir::new("execute_tally_vm_charges_bulk_memory_per_byte").unwrap();
+        let tempdir = temp_dir.path();
+        let execute = |wasm: Vec<u8>, gas_schedule: &GasScheduleVersion| {
+            _execute_tally_vm(
+                tempdir,
+                wasm,
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                gas_schedule.clone(),
+                None,
+            )
+            .unwrap()
+        };
+
+        // Without a per byte price the length makes no difference
+        let default = GasScheduleVersion::default();
+        assert_eq!(
+            execute(memory_fill(10), &default).gas_used,
+            execute(memory_fill(30), &default).gas_used
+        );
+
+        for bulk_memory_wasm in [memory_fill as fn(u32) -> Vec<u8>, memory_copy] {
+            let ten_bytes = execute(bulk_memory_wasm(10), &per_byte);
+            let thirty_bytes = execute(bulk_memory_wasm(30), &per_byte);
+            assert_eq!(ten_bytes.exit_info.exit_code, 0);
+            assert_eq!(thirty_bytes.exit_info.exit_code, 0);
+            assert_eq!(thirty_bytes.gas_used, ten_bytes.gas_used + 20_000_000_000_000);
+        }
+
+        // Writing more bytes than the gas limit pays for runs out of gas before anything is written
+        let too_many_bytes = execute(
+            memory_fill(60),
+            &GasScheduleVersion {
+                gas_schedule: GasSchedule {
+                    memory_bulk_per_byte: 10_000_000_000_000,
+                    ..Default::default()
+                },
+                ..per_byte.clone()
+            },
+        );
+        assert_eq!(too_many_bytes.exit_info.exit_code, 250);
+        assert_eq!(too_many_bytes.gas_used, 150_000_000_000_000);
+
+        // The built-in v2 prices every byte
+        let v2 = GasScheduleVersion::v2(1);
+        assert_eq!(
+            execute(memory_fill(30), &v2).gas_used,
+            execute(memory_fill(10), &v2).gas_used + 20 * v2.gas_schedule.memory_bulk_per_byte
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_charges_bulk_table_per_element() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+
+        let per_element = GasScheduleVersion {
+            name:              "v2".to_string(),
+            activation_height: 100,
+            gas_schedule:      GasSchedule {
+                table_bulk_per_element: 1_000_000_000_000,
+                ..Default::default()
+            },
+        };
+
+        let table_fill = |elements: u32| {
+            table_wasm(&format!(
+                "(table.fill (i32.const 0) (ref.func $f) (i32.const {elements}))"
+            ))
+        };
+        let table_copy = |elements: u32| {
+            table_wasm(&format!(
+                "(table.copy (i32.const 32) (i32.const 0) (i32.const {elements}))"
+            ))
+        };
+        let table_init = |elements: u32| {
+            table_wasm(&format!(
+                "(table.init 0 (i32.const 0) (i32.const 0) (i32.const {elements}))"
+            ))
+        };
+
+        let temp_dir = TempDir::new("execute_tally_vm_charges_bulk_table_per_element").unwrap();
+        let tempdir = temp_dir.path();
+        let execute = |wasm: Vec<u8>, gas_schedule: &GasScheduleVersion| {
+            _execute_tally_vm(
+                tempdir,
+                wasm,
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                gas_schedule.clone(),
+                None,
+            )
+            .unwrap()
+        };
+
+        // Without a per element price the length makes no difference
+        let default = GasScheduleVersion::default();
+        assert_eq!(
+            execute(table_fill(10), &default).gas_used,
+            execute(table_fill(30), &default).gas_used
+        );
+
+        let v2 = GasScheduleVersion::v2(1);
+        for bulk_table_wasm in [table_fill as fn(u32) -> Vec<u8>, table_copy, table_init] {
+            let ten_elements = execute(bulk_table_wasm(10), &per_element);
+            let thirty_elements = execute(bulk_table_wasm(30), &per_element);
+            assert_eq!(ten_elements.exit_info.exit_code, 0);
+            assert_eq!(thirty_elements.exit_info.exit_code, 0);
+            assert_eq!(thirty_elements.gas_used, ten_elements.gas_used + 20_000_000_000_000);
+
+            // The built-in v2 prices every element
+            assert_eq!(
+                execute(bulk_table_wasm(30), &v2).gas_used,
+                execute(bulk_table_wasm(10), &v2).gas_used + 20 * v2.gas_schedule.table_bulk_per_element
+            );
+        }
+
+        // Writing more elements than the gas limit pays for runs out of gas before anything is written
+        let too_many_elements = execute(
+            table_fill(60),
+            &GasScheduleVersion {
+                gas_schedule: GasSchedule {
+                    table_bulk_per_element: 10_000_000_000_000,
+                    ..Default::default()
+                },
+                ..per_element.clone()
+            },
+        );
+        assert_eq!(too_many_elements.exit_info.exit_code, 250);
+        assert_eq!(too_many_elements.gas_used, 150_000_000_000_000);
+    }
+
+    #[test]
+    fn gas_schedule_canonical_json() {
+        let gas_schedule = GasSchedule {
+            per_operation: 1,
+            ..Default::default()
+        };
+
+        let json = gas_schedule.canonical_json();
+        assert!(json.starts_with(b"{\"accounting_multiplier\":3000,\"args_get_base\":1000000000000,"));
+        assert_eq!(GasSchedule::from_json(&json).unwrap(), gas_schedule);
+        assert_ne!(gas_schedule.hash(), GasSchedule::default().hash());
+
+        // Every price has to be set, and nothing else
+        assert!(GasSchedule::from_json(b"{\"per_operation\":1}").is_err());
+        let mut json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        json["unknown_price"] = 1.into();
+        assert!(GasSchedule::from_json(json.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn execute_c_tally_vm_rejects_invalid_gas_schedules() {
+        let temp_dir = TempDir::new("execute_c_tally_vm_rejects_invalid_gas_schedules").unwrap();
+        let sedad_home = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let gas_schedules = CString::new("[{\"name\":\"v1\",\"activation_height\":0}]").unwrap();
+        let wasm_id = CString::new("not-a-cache-id").unwrap();
+        let call_id = CString::new("request-1").unwrap();
+
+        let ffi_settings = FfiVmSettings {
+            sedad_home:       sedad_home.as_ptr(),
+            max_result_bytes: 1024,
+            stdout_limit:     1024,
+            stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    gas_schedules.as_ptr(),
+        };
+        let ffi_request = FfiTallyRequest {
+            wasm_id:              wasm_id.as_ptr(),
+            wasm_bytes:           std::ptr::null(),
+            wasm_bytes_len:       0,
+            args_ptr:             std::ptr::null(),
+            args_count:           0,
+            env_keys_ptr:         std::ptr::null(),
+            env_values_ptr:       std::ptr::null(),
+            env_count:            0,
+            call_id:              call_id.as_ptr(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
+        };
+
+        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
+        assert_eq!(result.exit_info.exit_code, 251);
+        let exit_message = unsafe { CStr::from_ptr(result.exit_info.exit_message) }.to_string_lossy();
+        assert!(exit_message.contains("Invalid gas schedule"));
+        assert_eq!(unsafe { CStr::from_ptr(result.call_id) }, call_id.as_c_str());
+        unsafe { super::free_ffi_vm_result(&mut result) };
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn core_vm_does_not_expose_http_fetch() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+
+        let temp_dir = TempDir::new("core_vm_does_not_expose_http_fetch").unwrap();
+        let tempdir = temp_dir.path();
+
+        let call_data = VmCallData {
+            wasm_id: WasmId::Bytes(wasm_bytes.to_vec()),
+            args: vec![hex::encode("testHttpSuccess")],
+            vm_type: VmType::Core,
+            gas_limit: Some(150_000_000_000_000),
+            ..Default::default()
+        };
+
+        let runtime_context = RuntimeContext::new(tempdir, &call_data).unwrap();
+        let result = start_runtime(call_data, runtime_context, 1024, 1024);
+
+        assert_eq!(result.exit_info.exit_code, 15);
+        assert!(result
+            .exit_info
+            .exit_message
+            .contains("seda_v1::http_fetch (unknown function)"));
+        assert!(result
+            .exit_info
+            .exit_message
+            .contains("seda_v1::proxy_http_fetch (unknown function)"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn invalid_imports_are_reported() {
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("invalid_imports_are_reported").unwrap();
+        let tempdir = temp_dir.path();
+
+        let wasm_bytes = wat2wasm(
+            br#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "seda_v1" "keccak256" (func))
+                (import "env" "abort" (func)))"#,
+        )
+        .unwrap()
+        .into_owned();
+
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes,
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(result.exit_info.exit_code, 15);
+        assert!(result
+            .exit_info
+            .exit_message
+            .contains("seda_v1::keccak256 (signature mismatch, expected [] -> []"));
+        assert!(result.exit_info.exit_message.contains("env::abort (unknown namespace)"));
+        assert!(result.gas_used > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_no_args() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_no_args").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        result.stdout.iter().for_each(|line| print!("{}", line));
+        assert_eq!(result.gas_used, 10124565078750);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_with_low_gas_limit() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+        // enough to cover startup cost + some
+        let method_hex = hex::encode("testHttpSuccess");
+        let startup_gas = (method_hex.len() as u64 * 10_000) + (1_000_000_000_000 * 5);
+        let total_gas = startup_gas + 1_000;
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), total_gas.to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_with_low_gas_limit").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(result.exit_info.exit_code, 250);
+        assert_eq!(result.gas_used, total_gas);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn vm_does_not_run_if_startup_cost_is_higher_than_gas_limit() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+        // enough to cover startup cost + some
+        let method_hex = hex::encode("testHttpSuccess");
+        let startup_gas = (method_hex.len() as u64 * 10_000) + (1_000_000_000_000 * 5);
+        let total_gas = startup_gas - 1_000;
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), total_gas.to_string());
+
+        let temp_dir = TempDir::new("vm_does_not_run_if_startup_cost_is_higher_than_gas_limit").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(result.exit_info.exit_code, 14);
+        assert_eq!(
+            result.exit_info.exit_message,
+            "Error: Gas startup cost is too expensive. Args might be too large."
+        );
+        assert!(result.gas_used > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_vm_startup_gas_parts() {
+        let wasm_bytes = start_wasm("");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "tally".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_vm_startup_gas_parts").unwrap();
+        let tempdir = temp_dir.path();
+        let execute = |envs: &BTreeMap<String, String>, gas_schedule: GasSchedule| {
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes.clone(),
+                vec![],
+                envs.clone(),
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion {
+                    gas_schedule,
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap()
+        };
+
+        let base = execute(&envs, GasSchedule::default());
+        assert_eq!(base.exit_info.exit_code, 0);
+
+        let per_unit = 1_000_000;
+        let sized = |gas_schedule: GasSchedule| execute(&envs, gas_schedule).gas_used - base.gas_used;
+        assert_eq!(
+            sized(GasSchedule {
+                startup_per_wasm_byte: per_unit,
+                ..Default::default()
+            }),
+            wasm_bytes.len() as u64 * per_unit
+        );
+        assert_eq!(
+            sized(GasSchedule {
+                startup_per_memory_page: per_unit,
+                ..Default::default()
+            }),
+            per_unit
+        );
+
+        // Every env variable is charged for its key, its value and two terminators
+        let env_gas_schedule = GasSchedule {
+            startup_per_env_byte: per_unit,
+            ..Default::default()
+        };
+        let mut more_envs = envs.clone();
+        more_envs.insert("A".to_string(), "BC".to_string());
+        assert_eq!(
+            execute(&more_envs, env_gas_schedule.clone()).gas_used - execute(&envs, env_gas_schedule).gas_used,
+            5 * per_unit
+        );
+
+        // The built-in v2 prices the binary, the initial memory and the env variables
+        let v2 = GasSchedule::v2();
+        let unsized_v2 = GasSchedule {
+            startup_per_wasm_byte: 0,
+            startup_per_memory_page: 0,
+            ..v2.clone()
+        };
+        assert_eq!(
+            execute(&envs, v2.clone()).gas_used - execute(&envs, unsized_v2).gas_used,
+            wasm_bytes.len() as u64 * v2.startup_per_wasm_byte + v2.startup_per_memory_page
+        );
+        assert_eq!(
+            execute(&more_envs, v2.clone()).gas_used - execute(&envs, v2.clone()).gas_used,
+            5 * v2.startup_per_env_byte
+        );
+
+        let too_large = execute(
+            &envs,
+            GasSchedule {
+                startup_per_wasm_byte: 10_000_000_000_000,
+                ..Default::default()
+            },
+        );
+        assert_eq!(too_large.exit_info.exit_code, 14);
+        assert_eq!(
+            too_large.exit_info.exit_message,
+            "Error: Gas startup cost is too expensive. WASM binary might be too large."
+        );
+    }
+
+    #[test]
+    fn wasm_module_size() {
+        let message = [7u8; 100];
+        let wasm_bytes = host_call_wasm(
+            "keccak256",
+            &[HostCallArg::Bytes(&message, I32)],
+            HostCallOutput::CallResult,
+        );
+
+        assert_eq!(
+            WasmModuleSize::from_wasm(&wasm_bytes).unwrap(),
+            WasmModuleSize {
+                wasm_bytes:           wasm_bytes.len() as u64,
+                initial_memory_pages: 1,
+                data_segment_bytes:   100,
+            }
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_keccak256() {
+        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+        let mut envs: BTreeMap<String, String> = BTreeMap::new();
+        envs.insert("VM_MODE".to_string(), "dr".to_string());
+        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+
+        let temp_dir = TempDir::new("execute_tally_keccak256").unwrap();
+        let tempdir = temp_dir.path();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![hex::encode("testKeccak256")],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
+        result.stdout.iter().for_each(|line| print!("{}", line));
+
+        assert_eq!(
+            String::from_utf8(result.result.unwrap()).unwrap(),
+            // "testKeccak256" hashed
+            "fe8baa653979909c621153b53c973bab3832768b5e77896a5b5944d20d48c7a6"
+        );
+        assert_eq!(result.gas_used, 11250594475000);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_bn254_verify() {
+        let private_key = PrivateKey::try_from([7u8; 32].as_ref()).unwrap();
+        let public_key = PublicKey::from_private_key(&private_key).to_compressed().unwrap();
+        let message = b"seda overlay batch";
+        let signature = ECDSA::sign(message, &private_key).unwrap().to_compressed().unwrap();
+
+        let temp_dir = TempDir::new("execute_tally_bn254_verify").unwrap();
+        let tempdir = temp_dir.path();
+        let verify = |message: &[u8]| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let wasm_bytes = host_call_wasm(
+                "bn254_verify",
+                &[
+                    HostCallArg::Bytes(message, I64),
+                    HostCallArg::Bytes(&signature, I64),
+                    HostCallArg::Bytes(&public_key, I64),
+                ],
+                HostCallOutput::ReturnValue,
+            );
+
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+
+        let result = verify(message);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![1]));
+        assert!(result.gas_used > 1_000_000_000_000);
+
+        let result = verify(b"another message");
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![0]));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_secp256k1_recover() {
+        let signing_key = SigningKey::from_slice(&[9u8; 32]).unwrap();
+        let verifying_key = signing_key.verifying_key();
+        let expected_public_keys = [
+            verifying_key.to_encoded_point(true).as_bytes(),
+            verifying_key.to_encoded_point(false).as_bytes(),
+        ]
+        .concat();
+
+        let message = b"price is 42";
+        let (signature, recovery_id) = signing_key
+            .sign_digest_recoverable(Keccak256::new_with_prefix(message))
+            .unwrap();
+        // Same signature with the high s value, which flips the recovery id
+        let (r, s) = signature.split_scalars();
+        let high_s_signature = Signature::from_scalars(r, -s).unwrap();
+
+        let temp_dir = TempDir::new("execute_tally_secp256k1_recover").unwrap();
+        let tempdir = temp_dir.path();
+        let recover = |signature: &Signature, recovery_byte: u8, reject_high_s: i32| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let signature = [signature.to_bytes().as_slice(), &[recovery_byte]].concat();
+            let wasm_bytes = host_call_wasm(
+                "secp256k1_recover",
+                &[
+                    HostCallArg::Bytes(message, I64),
+                    HostCallArg::Bytes(&signature, I32),
+                    HostCallArg::I32(reject_high_s),
+                ],
+                HostCallOutput::CallResult,
+            );
+
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+
+        let result = recover(&signature, recovery_id.to_byte(), 1);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(expected_public_keys.clone()));
+
+        // EVM style recovery ids are accepted as well
+        let result = recover(&signature, recovery_id.to_byte() + 27, 1);
+        assert_eq!(result.result, Some(expected_public_keys.clone()));
+
+        let result = recover(&high_s_signature, recovery_id.to_byte() ^ 1, 0);
+        assert_eq!(result.result, Some(expected_public_keys));
+
+        let result = recover(&high_s_signature, recovery_id.to_byte() ^ 1, 1);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![]));
+
+        let result = recover(&signature, 4, 1);
+        assert_eq!(result.exit_info.exit_code, 252);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_tally_ed25519_verify() {
+        let signing_key = Ed25519SigningKey::from_bytes(&[5u8; 32]);
+        let public_key = signing_key.verifying_key().to_bytes();
+        let message = b"validator vote";
+        let signature = signing_key.sign(message).to_bytes();
+
+        let temp_dir = TempDir::new("execute_tally_ed25519_verify").unwrap();
+        let tempdir = temp_dir.path();
+        let verify = |message: &[u8], public_key: &[u8]| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let wasm_bytes = host_call_wasm(
+                "ed25519_verify",
+                &[
+                    HostCallArg::Bytes(message, I64),
+                    HostCallArg::Bytes(&signature, I32),
+                    HostCallArg::Bytes(public_key, I32),
+                ],
+                HostCallOutput::ReturnValue,
+            );
+
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
         };
 
-        let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
+        let result = verify(message, &public_key);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![1]));
+        assert!(result.gas_used > 1_000_000_000_000);
 
-        let exit_msg = unsafe {
-            CStr::from_ptr(result.exit_info.exit_message)
-                .to_string_lossy()
-                .into_owned()
-        };
-        assert_eq!(exit_msg, "Ok".to_string());
+        let result = verify(b"another vote", &public_key);
         assert_eq!(result.exit_info.exit_code, 0);
-        assert_eq!(result.gas_used, 9156653346250);
+        assert_eq!(result.result, Some(vec![0]));
 
-        unsafe {
-            super::free_ffi_vm_result(&mut result);
-            let tempdir_c = CString::from_raw(tempdir_craw);
-            mem::drop(tempdir_c);
-        }
+        // A public key has to be exactly 32 bytes
+        let result = verify(message, &public_key[..31]);
+        assert_eq!(result.exit_info.exit_code, 252);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn execute_tally_vm_proxy_http_fetch() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
-        let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
-        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
-
-        let temp_dir = TempDir::new("execute_tally_vm_proxy_http_fetch").unwrap();
+    async fn execute_tally_hash_imports() {
+        let temp_dir = TempDir::new("execute_tally_hash_imports").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(
-            tempdir,
-            wasm_bytes.to_vec(),
-            vec![hex::encode("testProxyHttpFetch")],
-            envs,
-            1024,
-            1024,
-        )
-        .unwrap();
 
-        result.stdout.iter().for_each(|line| print!("{}", line));
+        for (import, expected) in [
+            (
+                "sha256",
+                "a2821a22fdd44495155b3f4ba9dfa376998c81521815d24c02d210ba664b7030",
+            ),
+            (
+                "blake2b_256",
+                "e8945bebac9106f89e1959c5a4a3bfd68f43b1d3108e69b9b2eca21006bee8ad",
+            ),
+            ("ripemd160", "2d033ef51b72e31b21d83b35e442e0a0b1fc1123"),
+        ] {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let wasm_bytes = host_call_wasm(import, &[HostCallArg::Bytes(b"seda", I32)], HostCallOutput::CallResult);
+
+            let result = _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap();
 
-        assert_eq!(
-            String::from_utf8_lossy(&result.result.unwrap()),
-            "proxy_http_fetch is not allowed in tally".to_string()
-        );
-        assert_eq!(result.gas_used, 21736902545000);
+            assert_eq!(result.exit_info.exit_code, 0, "{import}");
+            // "seda" hashed
+            assert_eq!(hex::encode(result.result.unwrap()), expected, "{import}");
+            assert!(result.gas_used > 1_000_000_000_000);
+        }
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn execute_tally_vm_no_args() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/tally.wasm");
-        let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+    async fn execute_tally_merkle_verify() {
+        let keccak_leaf = |leaf: &[u8]| Keccak256::digest(leaf).to_vec();
+        let keccak_inner =
+            |left: &[u8], right: &[u8]| Keccak256::new_with_prefix(left).chain_update(right).finalize().to_vec();
+        let rfc6962_leaf = |leaf: &[u8]| Sha256::new_with_prefix([0x00]).chain_update(leaf).finalize().to_vec();
+        let rfc6962_inner = |left: &[u8], right: &[u8]| {
+            Sha256::new_with_prefix([0x01])
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .to_vec()
+        };
 
-        let temp_dir = TempDir::new("execute_tally_vm_no_args").unwrap();
+        let temp_dir = TempDir::new("execute_tally_merkle_verify").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![], envs, 1024, 1024).unwrap();
+        let verify = |root: &[u8], leaf: &[u8], proof: &[u8], algorithm: i32| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let wasm_bytes = host_call_wasm(
+                "merkle_verify",
+                &[
+                    HostCallArg::Bytes(root, I32),
+                    HostCallArg::Bytes(leaf, I32),
+                    HostCallArg::Bytes(proof, I32),
+                    HostCallArg::I32(algorithm),
+                ],
+                HostCallOutput::ReturnValue,
+            );
 
-        result.stdout.iter().for_each(|line| print!("{}", line));
-        assert_eq!(result.gas_used, 10124565078750);
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+
+        type HashLeaf<'a> = &'a dyn Fn(&[u8]) -> Vec<u8>;
+        type HashInner<'a> = &'a dyn Fn(&[u8], &[u8]) -> Vec<u8>;
+        let hashers: [(i32, HashLeaf, HashInner); 2] =
+            [(0, &keccak_leaf, &keccak_inner), (1, &rfc6962_leaf, &rfc6962_inner)];
+        for (algorithm, hash_leaf, hash_inner) in hashers {
+            // Tree of the leaves a, b, c and d, proving c: its sibling d is on the right, hash(a, b) on the left
+            let [a, b, c, d] = [b"a", b"b", b"c", b"d"].map(|leaf| hash_leaf(&leaf[..]));
+            let ab = hash_inner(&a, &b);
+            let root = hash_inner(&ab, &hash_inner(&c, &d));
+            let proof = [&[1][..], &d[..], &[0][..], &ab[..]].concat();
+
+            let result = verify(&root, b"c", &proof, algorithm);
+            assert_eq!(result.exit_info.exit_code, 0);
+            assert_eq!(result.result, Some(vec![1]));
+            assert!(result.gas_used > 1_000_000_000_000);
+
+            let result = verify(&root, b"d", &proof, algorithm);
+            assert_eq!(result.exit_info.exit_code, 0);
+            assert_eq!(result.result, Some(vec![0]));
+
+            // Every proof node has to carry a side byte
+            let result = verify(&root, b"c", &[&d[..], &ab[..]].concat(), algorithm);
+            assert_eq!(result.exit_info.exit_code, 252);
+        }
+
+        // Without domain separation an inner node could pass as a 64-byte keccak256 leaf
+        let [a, b] = [b"a", b"b"].map(|leaf| keccak_leaf(&leaf[..]));
+        let root = keccak_inner(&a, &b);
+        let inner_node = [a, b].concat();
+        let result = verify(&root, &inner_node, &[], 0);
+        assert_eq!(result.exit_info.exit_code, 252);
+        let result = verify(&root, &inner_node, &[], 1);
+        assert_eq!(result.exit_info.exit_code, 0);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn execute_tally_vm_with_low_gas_limit() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
-        let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
-        // enough to cover startup cost + some
-        let method_hex = hex::encode("testHttpSuccess");
-        let startup_gas = (method_hex.len() as u64 * 10_000) + (1_000_000_000_000 * 5);
-        let total_gas = startup_gas + 1_000;
-        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), total_gas.to_string());
+    async fn execute_tally_merkle_verify_ics23() {
+        let pack = |values: &[&[u8]]| {
+            values
+                .iter()
+                .flat_map(|value| [&(value.len() as u32).to_le_bytes()[..], value].concat())
+                .collect::<Vec<u8>>()
+        };
+        // The `LeafOp` of the IAVL spec: no key prehash, sha256 value prehash and varint length prefixes
+        let leaf_prefix = [0x00, 0x02, 0x02, 0x0a];
+        let key = b"store/key";
+        let value = b"value";
+        let leaf_hash = Sha256::new_with_prefix(leaf_prefix)
+            .chain_update([key.len() as u8])
+            .chain_update(key)
+            .chain_update([32])
+            .chain_update(Sha256::digest(value))
+            .finalize();
+        let sibling = Sha256::digest(b"sibling");
+        // The node is on the left in the first `InnerOp` and on the right in the second one
+        let inner_ops: [(Vec<u8>, Vec<u8>); 2] = [
+            (vec![0x02, 0x04, 0x0a, 0x20], [&[0x20][..], &sibling[..]].concat()),
+            (
+                [&[0x04, 0x08, 0x0a, 0x20][..], &sibling[..], &[0x20][..]].concat(),
+                vec![],
+            ),
+        ];
+        let root = inner_ops.iter().fold(leaf_hash.to_vec(), |node, (prefix, suffix)| {
+            Sha256::new_with_prefix(prefix)
+                .chain_update(node)
+                .chain_update(suffix)
+                .finalize()
+                .to_vec()
+        });
+        let proof = pack(&[&inner_ops[0].0, &inner_ops[0].1, &inner_ops[1].0, &inner_ops[1].1]);
 
-        let temp_dir = TempDir::new("execute_tally_vm_with_low_gas_limit").unwrap();
+        let temp_dir = TempDir::new("execute_tally_merkle_verify_ics23").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let verify = |leaf: &[u8], proof: &[u8]| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
+            let wasm_bytes = host_call_wasm(
+                "merkle_verify",
+                &[
+                    HostCallArg::Bytes(&root, I32),
+                    HostCallArg::Bytes(leaf, I32),
+                    HostCallArg::Bytes(proof, I32),
+                    HostCallArg::I32(2),
+                ],
+                HostCallOutput::ReturnValue,
+            );
 
-        assert_eq!(result.exit_info.exit_code, 250);
-        assert_eq!(result.gas_used, total_gas);
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+
+        let result = verify(&pack(&[key, value, &leaf_prefix]), &proof);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![1]));
+
+        let result = verify(&pack(&[key, b"other value", &leaf_prefix]), &proof);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![0]));
+
+        // Leaf prefixes start with 0x00, inner prefixes must not, so one cannot pass as the other
+        let result = verify(&pack(&[key, value, &[0x02]]), &proof);
+        assert_eq!(result.exit_info.exit_code, 252);
+        let result = verify(&pack(&[key, value, &leaf_prefix]), &pack(&[&[0x00], &inner_ops[0].1]));
+        assert_eq!(result.exit_info.exit_code, 252);
+
+        // Values have to be length prefixed
+        let result = verify(&pack(&[key, value, &leaf_prefix]), &proof[..proof.len() - 1]);
+        assert_eq!(result.exit_info.exit_code, 252);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn vm_does_not_run_if_startup_cost_is_higher_than_gas_limit() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
-        let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
-        // enough to cover startup cost + some
-        let method_hex = hex::encode("testHttpSuccess");
-        let startup_gas = (method_hex.len() as u64 * 10_000) + (1_000_000_000_000 * 5);
-        let total_gas = startup_gas - 1_000;
-        envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), total_gas.to_string());
+    async fn execute_tally_secp256k1_verify_batch() {
+        let signing_key = SigningKey::from_slice(&[3u8; 32]).unwrap();
+        let public_key = signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec();
+        let sign = |message: &[u8]| -> Vec<u8> {
+            let (signature, _) = signing_key
+                .sign_digest_recoverable(Keccak256::new_with_prefix(message))
+                .unwrap();
+            signature.to_bytes().to_vec()
+        };
 
-        let temp_dir = TempDir::new("vm_does_not_run_if_startup_cost_is_higher_than_gas_limit").unwrap();
+        let entries = [
+            (b"reveal 0".to_vec(), sign(b"reveal 0"), public_key.clone()),
+            (b"reveal 1".to_vec(), sign(b"another reveal"), public_key.clone()),
+            (b"reveal 2".to_vec(), sign(b"reveal 2"), public_key[..32].to_vec()),
+            (b"reveal 3".to_vec(), sign(b"reveal 3"), public_key.clone()),
+        ];
+        let packed = entries
+            .iter()
+            .flat_map(|(message, signature, public_key)| [message, signature, public_key])
+            .flat_map(|value| [&(value.len() as u32).to_le_bytes()[..], &value[..]].concat())
+            .collect::<Vec<u8>>();
+
+        let temp_dir = TempDir::new("execute_tally_secp256k1_verify_batch").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let verify_batch = |entries_count: i32| {
+            let mut envs: BTreeMap<String, String> = BTreeMap::new();
+            envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "20000000000000".to_string()); // 20 tGas
+            let wasm_bytes = host_call_wasm(
+                "secp256k1_verify_batch",
+                &[HostCallArg::Bytes(&packed, I32), HostCallArg::I32(entries_count)],
+                HostCallOutput::CallResult,
+            );
 
-        assert_eq!(result.exit_info.exit_code, 14);
-        assert!(result.gas_used > 0);
+            _execute_tally_vm(
+                tempdir,
+                wasm_bytes,
+                vec![],
+                envs,
+                1024,
+                1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
+            )
+            .unwrap()
+        };
+
+        // Only the first and the last signature are valid
+        let result = verify_batch(4);
+        assert_eq!(result.exit_info.exit_code, 0);
+        assert_eq!(result.result, Some(vec![0b1001]));
+
+        // The packed entries have to match the count
+        let result = verify_batch(3);
+        assert_eq!(result.exit_info.exit_code, 252);
+        let result = verify_batch(5);
+        assert_eq!(result.exit_info.exit_code, 252);
+
+        // Gas for the whole batch is checked before any signature is verified
+        let result = verify_batch(i32::MAX);
+        assert_eq!(result.exit_info.exit_code, 252);
+        assert_eq!(
+            result.stderr[0],
+            "Runtime error: Malformed entries in secp256k1_verify_batch"
+        );
+        let result = verify_batch(38);
+        assert_eq!(result.exit_info.exit_code, 252);
+        assert_eq!(result.stderr[0], "Runtime error: Out of gas");
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn execute_tally_keccak256() {
-        let wasm_bytes = include_bytes!("../../test-wasm-files/integration-test.wasm");
+    async fn seda_v2_call_result_handles() {
         let mut envs: BTreeMap<String, String> = BTreeMap::new();
-        envs.insert("VM_MODE".to_string(), "dr".to_string());
         envs.insert(DEFAULT_GAS_LIMIT_ENV_VAR.to_string(), "150000000000000".to_string());
 
-        let temp_dir = TempDir::new("execute_tally_keccak256").unwrap();
+        let temp_dir = TempDir::new("seda_v2_call_result_handles").unwrap();
         let tempdir = temp_dir.path();
+
+        // Hashes "seda" with keccak256 and sha256 under seda_v2, keeping both handles, then writes the sha256 result
+        // to address 64 and the keccak256 result to address 32 and reports both as the execution result
+        let wasm_bytes = wat2wasm(
+            br#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "seda_v2" "keccak256" (func $keccak256 (param i32 i32) (result i32)))
+                (import "seda_v2" "sha256" (func $sha256 (param i32 i32) (result i32)))
+                (import "seda_v2" "call_result_length" (func $call_result_length (param i32) (result i32)))
+                (import "seda_v2" "call_result_write" (func $call_result_write (param i32 i32 i32)))
+                (import "seda_v1" "execution_result" (func $execution_result (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "seda")
+                (func (export "_start") (local $keccak256 i32) (local $sha256 i32)
+                    (local.set $keccak256 (call $keccak256 (i32.const 0) (i32.const 4)))
+                    (local.set $sha256 (call $sha256 (i32.const 0) (i32.const 4)))
+                    (call $call_result_write
+                        (local.get $sha256)
+                        (i32.const 64)
+                        (call $call_result_length (local.get $sha256)))
+                    (call $call_result_write
+                        (local.get $keccak256)
+                        (i32.const 32)
+                        (call $call_result_length (local.get $keccak256)))
+                    (call $execution_result (i32.const 32) (i32.const 64))))"#,
+        )
+        .unwrap()
+        .into_owned();
+
+        let (_, report) = _compile_wasm_program(
+            tempdir,
+            wasm_bytes.clone(),
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+        )
+        .unwrap();
+        assert!(report.disallowed_imports.is_empty());
+
         let result = _execute_tally_vm(
             tempdir,
-            wasm_bytes.to_vec(),
-            vec![hex::encode("testKeccak256")],
+            wasm_bytes,
+            vec![],
             envs,
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
-        result.stdout.iter().for_each(|line| print!("{}", line));
 
+        assert_eq!(result.exit_info.exit_code, 0);
         assert_eq!(
-            String::from_utf8(result.result.unwrap()).unwrap(),
-            // "testKeccak256" hashed
-            "fe8baa653979909c621153b53c973bab3832768b5e77896a5b5944d20d48c7a6"
+            result.result.unwrap(),
+            [Keccak256::digest(b"seda").to_vec(), Sha256::digest(b"seda").to_vec()].concat()
         );
-        assert_eq!(result.gas_used, 11250594475000);
+        // The newest namespace the program imports from
+        assert_eq!(result.abi_version, Some(AbiVersion::V2));
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -998,6 +3626,9 @@ mod test {
             envs,
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
         result.stdout.iter().for_each(|line| print!("{}", line));
@@ -1018,7 +3649,18 @@ mod test {
 
         let temp_dir = TempDir::new("polyfill_does_not_crash_vm").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         result.stdout.iter().for_each(|line| print!("{}", line));
 
         assert_eq!(result.exit_info.exit_code, 252);
@@ -1046,8 +3688,7 @@ mod test {
             ],
             envs,
             1024,
-            1024
-        ).unwrap();
+            1024, WasmEngine::Singlepass, GasScheduleVersion::default(), None).unwrap();
 
         assert_eq!(result.exit_info.exit_code, 1);
         assert_eq!(result.exit_info.exit_message, "Not ok".to_string());
@@ -1074,8 +3715,7 @@ mod test {
             ],
             envs,
             1024,
-            1024,
-        ).unwrap();
+            1024, WasmEngine::Singlepass, GasScheduleVersion::default(), None).unwrap();
 
         assert_eq!(result.exit_info.exit_code, 4);
         assert_eq!(result.exit_info.exit_message, "Error: Failed to create WASMER instance: Insufficient resources: Failed to create memory: A user-defined error occurred: Minimum exceeds the allowed memory limit".to_string());
@@ -1094,7 +3734,18 @@ mod test {
         let method = "import_length_overflow".to_string();
         let method_hex = hex::encode(method.to_bytes().eject());
 
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.stderr[0], "Runtime error: Out of gas");
         assert!(result.gas_used > 0);
@@ -1123,6 +3774,9 @@ mod test {
             envs,
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
         assert_eq!(result.gas_used, 14000427996250);
@@ -1140,7 +3794,18 @@ mod test {
 
         let temp_dir = TempDir::new("call_result_write_len_0").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.exit_info.exit_code, 252);
         assert_eq!(result.exit_info.exit_message, "Not ok".to_string());
@@ -1185,16 +3850,22 @@ mod test {
             max_result_bytes: 1024,
             stdout_limit:     1024,
             stderr_limit:     1024,
+            wasm_engine:      FfiWasmEngine::Singlepass,
+            gas_schedules:    std::ptr::null(),
         };
 
         let ffi_request = FfiTallyRequest {
-            wasm_bytes:     wasm_bytes.as_ptr(),
-            wasm_bytes_len: wasm_bytes.len(),
-            args_ptr:       arg_ptrs.as_ptr(),
-            args_count:     args.len(),
-            env_keys_ptr:   env_key_ptrs.as_ptr(),
-            env_values_ptr: env_value_ptrs.as_ptr(),
-            env_count:      envs.len(),
+            wasm_id:              std::ptr::null(),
+            wasm_bytes:           wasm_bytes.as_ptr(),
+            wasm_bytes_len:       wasm_bytes.len(),
+            args_ptr:             arg_ptrs.as_ptr(),
+            args_count:           args.len(),
+            env_keys_ptr:         env_key_ptrs.as_ptr(),
+            env_values_ptr:       env_value_ptrs.as_ptr(),
+            env_count:            envs.len(),
+            call_id:              std::ptr::null(),
+            gas_schedule_version: std::ptr::null(),
+            block_height:         0,
         };
 
         let mut result = unsafe { super::execute_tally_request(ffi_settings, ffi_request) };
@@ -1230,7 +3901,18 @@ mod test {
 
         let temp_dir = TempDir::new("test_stdout_and_stderr_limit").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 2, 2).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            2,
+            2,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.exit_info.exit_code, 0);
         assert_eq!(result.stdout.len(), 1);
@@ -1253,7 +3935,18 @@ mod test {
 
         let temp_dir = TempDir::new("test_long_stdout_and_stderr").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.exit_info.exit_code, 0);
         assert_eq!(result.stdout.len(), 1);
@@ -1278,8 +3971,18 @@ mod test {
         let method = "stderr_non_utf8".to_string();
         let method_hex = hex::encode(method.to_bytes().eject());
 
-        let result =
-            _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs.clone(), 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         assert_eq!(result.exit_info.exit_code, 8);
         assert_eq!(result.stderr.len(), 0);
         assert_eq!(
@@ -1291,7 +3994,18 @@ mod test {
         let method = "stdout_non_utf8".to_string();
         let method_hex = hex::encode(method.to_bytes().eject());
 
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         assert_eq!(result.exit_info.exit_code, 8);
         assert_eq!(result.stdout.len(), 0);
         assert_eq!(
@@ -1314,8 +4028,18 @@ mod test {
         let method = "cannot_spam_call_result_write".to_string();
         let method_hex = hex::encode(method.to_bytes().eject());
 
-        let result =
-            _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs.clone(), 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         assert_eq!(result.exit_info.exit_code, 252);
         assert_eq!(result.stderr.len(), 1);
         assert_eq!(result.stderr[0], "Runtime error: Invalid Memory Access: call_result_write: result_data_ptr length does not match call_value length");
@@ -1336,8 +4060,18 @@ mod test {
         let method = "clock_time_get".to_string();
         let method_hex = hex::encode(method.to_bytes().eject());
 
-        let result =
-            _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs.clone(), 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs.clone(),
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         assert_eq!(result.exit_info.exit_code, 252);
         assert_eq!(&result.exit_info.exit_message, "Not ok");
         assert!(
@@ -1359,7 +4093,18 @@ mod test {
         let temp_dir = TempDir::new("timing_call_infinite_loop").unwrap();
         let tempdir = temp_dir.path();
         let start = std::time::Instant::now();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         let elapsed = start.elapsed();
 
         assert_eq!(result.exit_info.exit_code, 252);
@@ -1394,6 +4139,9 @@ mod test {
             envs,
             1024,
             1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
         )
         .unwrap();
         result.stdout.iter().for_each(|line| print!("{}", line));
@@ -1412,7 +4160,18 @@ mod test {
         let tempdir = temp_dir.path();
 
         let start = std::time::Instant::now();
-        let _result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![], envs, 1024, 1024).unwrap();
+        let _result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
         let duration = start.elapsed();
 
         assert!(
@@ -1434,7 +4193,18 @@ mod test {
 
         let temp_dir = TempDir::new("memory_fill_prealloc").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.exit_info.exit_code, 252);
         assert_eq!(result.stderr[0], "memory allocation of 44832551 bytes failed\n");
@@ -1452,7 +4222,18 @@ mod test {
 
         let temp_dir = TempDir::new("memory_fill_dynamic").unwrap();
         let tempdir = temp_dir.path();
-        let result = _execute_tally_vm(tempdir, wasm_bytes.to_vec(), vec![method_hex], envs, 1024, 1024).unwrap();
+        let result = _execute_tally_vm(
+            tempdir,
+            wasm_bytes.to_vec(),
+            vec![method_hex],
+            envs,
+            1024,
+            1024,
+            WasmEngine::Singlepass,
+            GasScheduleVersion::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.exit_info.exit_code, 252);
         assert_eq!(result.stderr[0], "memory allocation of 8192000 bytes failed\n");
@@ -1483,6 +4264,9 @@ mod test {
                 envs.clone(),
                 1024,
                 1024,
+                WasmEngine::Singlepass,
+                GasScheduleVersion::default(),
+                None,
             )
             .unwrap();
         }
@@ -1500,17 +4284,19 @@ mod test {
         // Create a test case that reproduces the NulError
         // This simulates the scenario where stdout contains null bytes
         let vm_result = VmResult {
-            stdout:    vec![
+            stdout:      vec![
                 "Received response: @B\x0f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\n".to_string(),
                 "Final consensus response: @B\x0f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\n".to_string(),
             ],
-            stderr:    vec![],
-            result:    Some(vec![]),
-            exit_info: ExitInfo {
+            stderr:      vec![],
+            result:      Some(vec![]),
+            exit_info:   ExitInfo {
                 exit_message: "Success".to_string(),
                 exit_code:    0,
             },
-            gas_used:  0,
+            gas_used:    0,
+            call_id:     None,
+            abi_version: None,
         };
 
         // Convert to FfiVmResult
@@ -1527,17 +4313,19 @@ mod test {
         // Create a test case that reproduces the NulError
         // This simulates the scenario where stdout contains null bytes
         let vm_result = VmResult {
-            stdout:    vec![],
-            stderr:    vec![
+            stdout:      vec![],
+            stderr:      vec![
                 "Received response: @B\x0f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\n".to_string(),
                 "Final consensus response: @B\x0f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\n".to_string(),
             ],
-            result:    Some(vec![]),
-            exit_info: ExitInfo {
+            result:      Some(vec![]),
+            exit_info:   ExitInfo {
                 exit_message: "Success".to_string(),
                 exit_code:    0,
             },
-            gas_used:  0,
+            gas_used:    0,
+            call_id:     None,
+            abi_version: None,
         };
 
         // Convert to FfiVmResult
@@ -1576,14 +4364,16 @@ mod test {
 
         for (test_string, description) in test_cases {
             let vm_result = VmResult {
-                stdout:    vec![test_string.to_string()],
-                stderr:    vec![],
-                result:    Some(vec![]),
-                exit_info: ExitInfo {
+                stdout:      vec![test_string.to_string()],
+                stderr:      vec![],
+                result:      Some(vec![]),
+                exit_info:   ExitInfo {
                     exit_message: test_string.to_string(),
                     exit_code:    0,
                 },
-                gas_used:  0,
+                gas_used:    0,
+                call_id:     None,
+                abi_version: None,
             };
 
             // This should not panic for any of these cases
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use wasmer::{Exports, FunctionEnv, Module, Store};
+
+use crate::{core_vm_imports::create_seda_v2_exports, VmContext};
+
+/// A version of the SEDA host ABI. Every version is exposed under its own import namespace, so new imports can ship
+/// in a new version without changing what already deployed programs link against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
+pub enum AbiVersion {
+    /// `seda_v1`, imports with a result return its length and share a single call result slot
+    V1,
+    /// `seda_v2`, imports with a result return a handle to it
+    V2,
+}
+
+impl AbiVersion {
+    /// Every version the VM provides, oldest first
+    pub const ALL: [AbiVersion; 2] = [AbiVersion::V1, AbiVersion::V2];
+
+    pub fn namespace(&self) -> &'static str {
+        match self {
+            AbiVersion::V1 => "seda_v1",
+            AbiVersion::V2 => "seda_v2",
+        }
+    }
+
+    pub fn from_namespace(namespace: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|version| version.namespace() == namespace)
+    }
+
+    /// The version number, as reported over FFI
+    pub fn number(&self) -> u32 {
+        match self {
+            AbiVersion::V1 => 1,
+            AbiVersion::V2 => 2,
+        }
+    }
+
+    /// Imports only this version provides, on top of the ones allowed for the VM type
+    fn extra_imports(&self) -> &'static [&'static str] {
+        match self {
+            // `seda_v1` returns the call result length from the import itself
+            AbiVersion::V1 => &[],
+            AbiVersion::V2 => &["call_result_length"],
+        }
+    }
+
+    /// Whether this version provides the host import `name`, given the imports allowed for the VM type
+    pub fn provides(&self, name: &str, allowed_imports: &[String]) -> bool {
+        self.extra_imports().contains(&name) || allowed_imports.iter().any(|import| import == name)
+    }
+
+    /// Builds the exports of this version from the allowed `seda_v1` host exports
+    pub(crate) fn create_exports(
+        &self,
+        store: &mut Store,
+        vm_context: &FunctionEnv<VmContext>,
+        seda_v1_exports: &Exports,
+    ) -> Exports {
+        match self {
+            AbiVersion::V1 => seda_v1_exports.clone(),
+            AbiVersion::V2 => create_seda_v2_exports(store, vm_context, seda_v1_exports),
+        }
+    }
+}
+
+/// Every ABI version the module imports from, oldest first
+pub fn imported_abi_versions(module: &Module) -> Vec<AbiVersion> {
+    AbiVersion::ALL
+        .into_iter()
+        .filter(|version| module.imports().any(|import| import.module() == version.namespace()))
+        .collect()
+}
+
+/// The ABI version a module was built against, which is the newest version it imports from.
+/// `None` when the module does not use any SEDA host imports.
+pub fn detect_abi_version(module: &Module) -> Option<AbiVersion> {
+    imported_abi_versions(module).last().copied()
+}
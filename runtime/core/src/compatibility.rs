@@ -0,0 +1,112 @@
+use wasmer::{AsStoreRef, ExternType, Imports, Module, Pages};
+use wasmer_wasix::get_wasi_version;
+
+use crate::{
+    abi::AbiVersion,
+    vm::VmType,
+    vm_imports::{safe_imports, POLYFILLED_WASI_IMPORTS},
+    SAFE_WASI_IMPORTS,
+};
+
+/// Everything that would prevent a compiled program from running in the VM
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Imports the VM does not provide, formatted as `namespace::name`
+    pub disallowed_imports:    Vec<String>,
+    pub missing_memory_export: bool,
+    pub missing_start_export:  bool,
+    /// The initial or maximum memory exceeds the memory limit of the VM
+    pub memory_too_large:      bool,
+}
+
+impl CompatibilityReport {
+    pub fn is_compatible(&self) -> bool {
+        self.disallowed_imports.is_empty()
+            && !self.missing_memory_export
+            && !self.missing_start_export
+            && !self.memory_too_large
+    }
+}
+
+/// Checks whether an import is provided for the given VM type, this mirrors how
+/// `create_wasm_imports` picks the host and WASI imports.
+pub fn is_allowed_import(module: &Module, vm_type: &VmType, namespace: &str, name: &str) -> bool {
+    let allowed_imports = safe_imports(vm_type);
+    let is_wasi_import = SAFE_WASI_IMPORTS.iter().any(|import| import == name);
+
+    if let Some(abi_version) = AbiVersion::from_namespace(namespace) {
+        return !is_wasi_import && abi_version.provides(name, allowed_imports);
+    }
+
+    let Some(wasi_version) = get_wasi_version(module, false) else {
+        return false;
+    };
+
+    namespace == wasi_version.get_namespace_str() && (is_wasi_import || POLYFILLED_WASI_IMPORTS.contains(&name))
+}
+
+pub fn check_compatibility(module: &Module, vm_type: &VmType, max_memory_pages: u32) -> CompatibilityReport {
+    let disallowed_imports = module
+        .imports()
+        .filter(|import| !is_allowed_import(module, vm_type, import.module(), import.name()))
+        .map(|import| format!("{}::{}", import.module(), import.name()))
+        .collect();
+
+    let memory = module.exports().find_map(|export| match export.ty() {
+        ExternType::Memory(memory) if export.name() == "memory" => Some(*memory),
+        _ => None,
+    });
+
+    let has_start = module
+        .exports()
+        .any(|export| export.name() == "_start" && matches!(export.ty(), ExternType::Function(_)));
+
+    let limit = Pages(max_memory_pages);
+    let memory_too_large =
+        memory.is_some_and(|memory| memory.minimum > limit || memory.maximum.is_some_and(|maximum| maximum > limit));
+
+    CompatibilityReport {
+        disallowed_imports,
+        missing_memory_export: memory.is_none(),
+        missing_start_export: !has_start,
+        memory_too_large,
+    }
+}
+
+fn describe_extern_type(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Function(function) => function.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Checks every import of the module against the imports the VM is about to provide,
+/// returns a description of each import that would make instantiation fail.
+pub fn validate_imports(store: &impl AsStoreRef, module: &Module, imports: &Imports) -> Vec<String> {
+    module
+        .imports()
+        .filter_map(|import| {
+            let (namespace, name) = (import.module(), import.name());
+
+            let Some(provided) = imports.get_export(namespace, name) else {
+                let reason = match imports.get_namespace_exports(namespace) {
+                    Some(_) => "unknown function",
+                    None => "unknown namespace",
+                };
+
+                return Some(format!("{namespace}::{name} ({reason})"));
+            };
+
+            let provided = provided.ty(store);
+            if &provided != import.ty() {
+                return Some(format!(
+                    "{namespace}::{name} (signature mismatch, expected {} but the VM provides {})",
+                    describe_extern_type(import.ty()),
+                    describe_extern_type(&provided),
+                ));
+            }
+
+            None
+        })
+        .collect()
+}
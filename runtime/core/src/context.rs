@@ -1,10 +1,52 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use parking_lot::{Mutex, RwLock};
 use wasmer::{AsStoreRef, FunctionEnv, Imports, Instance, Memory, MemoryView, Store};
 use wasmer_wasix::{WasiEnv, WasiVersion};
 
-use crate::vm::VmCallData;
+use crate::{errors::Result, http_fetcher::HttpFetcher, vm::VmCallData, RuntimeError};
+
+/// How many call results can be waiting to be written at the same time
+const MAX_PENDING_CALL_RESULTS: usize = 32;
+
+/// Results of `seda_v2` host calls that have not been written to the guest yet, keyed by handle
+#[derive(Default)]
+pub struct CallResults {
+    next_handle: u32,
+    pending:     HashMap<u32, Vec<u8>>,
+}
+
+impl CallResults {
+    /// Stores a result and returns the handle the guest can use to read it, handles are never reused
+    pub fn insert(&mut self, value: Vec<u8>) -> Result<u32> {
+        if self.pending.len() >= MAX_PENDING_CALL_RESULTS {
+            return Err(RuntimeError::Unknown(format!(
+                "Too many pending call results (max {MAX_PENDING_CALL_RESULTS})"
+            )));
+        }
+
+        self.next_handle = self
+            .next_handle
+            .checked_add(1)
+            .ok_or_else(|| RuntimeError::Unknown("Ran out of call result handles".to_string()))?;
+        self.pending.insert(self.next_handle, value);
+
+        Ok(self.next_handle)
+    }
+
+    pub fn get(&self, handle: u32) -> Result<&Vec<u8>> {
+        self.pending
+            .get(&handle)
+            .ok_or_else(|| RuntimeError::Unknown(format!("Unknown call result handle: {handle}")))
+    }
+
+    /// Removes a result, its handle is invalid afterwards
+    pub fn take(&mut self, handle: u32) -> Result<Vec<u8>> {
+        self.pending
+            .remove(&handle)
+            .ok_or_else(|| RuntimeError::Unknown(format!("Unknown call result handle: {handle}")))
+    }
+}
 
 #[derive(Clone)]
 pub struct VmContext {
@@ -24,7 +66,12 @@ pub struct VmContext {
     /// use these 3 calls in sequental we are fine, but it could crash if the
     /// order changes.
     pub call_result_value: Arc<RwLock<Vec<u8>>>,
+    /// Handle based results of the `seda_v2` imports, several of them can be pending at once
+    pub call_results:      Arc<Mutex<CallResults>>,
     pub instance:          Option<Instance>,
+
+    /// Performs the HTTP requests in data request mode, unused by the other modes
+    pub http_fetcher: Option<Arc<dyn HttpFetcher>>,
 }
 
 impl VmContext {
@@ -33,6 +80,7 @@ impl VmContext {
         store: &mut Store,
         wasi_env: FunctionEnv<WasiEnv>,
         call_data: VmCallData,
+        http_fetcher: Option<Arc<dyn HttpFetcher>>,
     ) -> FunctionEnv<VmContext> {
         FunctionEnv::new(
             store,
@@ -41,10 +89,12 @@ impl VmContext {
                 memory: None,
                 wasi_env,
                 call_result_value: Arc::new(RwLock::new(Vec::new())),
+                call_results: Default::default(),
                 instance: None,
                 wasi_imports: None,
                 call_data,
                 wasi_version: None,
+                http_fetcher,
             },
         )
     }
@@ -0,0 +1,30 @@
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+
+use crate::{errors::Result, metering::apply_gas_cost, VmContext};
+
+pub fn blake2b_256_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn blake2b_256(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        message_ptr: WasmPtr<u8>,
+        message_length: u32,
+    ) -> Result<u32> {
+        apply_gas_cost(
+            crate::metering::ExternalCallType::Blake2b256(message_length as u64),
+            &mut env,
+        )?;
+
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        let message = message_ptr.slice(&memory, message_length)?.read_to_vec()?;
+        let hash = Blake2b::<U32>::digest(message);
+
+        let mut call_value = ctx.call_result_value.write();
+        *call_value = hash.to_vec();
+
+        Ok(call_value.len() as u32)
+    }
+
+    Function::new_typed_with_env(store, vm_context, blake2b_256)
+}
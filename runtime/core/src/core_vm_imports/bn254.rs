@@ -0,0 +1,57 @@
+use bn254::{PublicKey, Signature, ECDSA};
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+
+use crate::{context::VmContext, errors::Result, metering::apply_gas_cost, RuntimeError};
+
+/// Verifies a `BN254` BLS signature.
+///
+/// Inputs:
+///     - message (any payload in bytes, hashed to the G1 curve)
+///     - signature (bytes as a compressed point on the G1 curve)
+///     - public_key (bytes as a compressed point on the G2 curve)
+///
+/// Output:
+///     - u8 (boolean, 1 for true)
+pub fn bn254_verify_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn bn254_verify(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        message: WasmPtr<u8>,
+        message_length: i64,
+        signature: WasmPtr<u8>,
+        signature_length: i64,
+        public_key: WasmPtr<u8>,
+        public_key_length: i64,
+    ) -> Result<u8> {
+        // Lengths have to be positive and fit in the 32-bit address space of the guest
+        let (Ok(message_length), Ok(signature_length), Ok(public_key_length)) = (
+            u32::try_from(message_length),
+            u32::try_from(signature_length),
+            u32::try_from(public_key_length),
+        ) else {
+            return Err(RuntimeError::Unknown(
+                "Invalid length provided to bn254_verify".to_string(),
+            ));
+        };
+
+        apply_gas_cost(
+            crate::metering::ExternalCallType::Bn254Verify(message_length as u64),
+            &mut env,
+        )?;
+
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        // Fetch function arguments as Vec<u8>
+        let message = message.slice(&memory, message_length)?.read_to_vec()?;
+        let signature = signature.slice(&memory, signature_length)?.read_to_vec()?;
+        let public_key = public_key.slice(&memory, public_key_length)?.read_to_vec()?;
+
+        // `BN254` verification (the message is hashed to G1 by the library)
+        let signature_obj = Signature::from_compressed(&signature)?;
+        let public_key_obj = PublicKey::from_compressed(&public_key)?;
+
+        Ok(ECDSA::verify(&message, &signature_obj, &public_key_obj).is_ok().into())
+    }
+
+    Function::new_typed_with_env(store, vm_context, bn254_verify)
+}
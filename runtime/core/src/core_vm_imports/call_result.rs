@@ -1,4 +1,4 @@
-use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, Value, WasmPtr};
 
 use crate::{context::VmContext, errors::Result, RuntimeError};
 
@@ -54,3 +54,66 @@ pub fn call_result_value_write_import_obj(store: &mut Store, vm_context: &Functi
 
     Function::new_typed_with_env(store, vm_context, call_result_value)
 }
+
+/// Wraps a `seda_v1` import that leaves its result in `call_result_value` and returns its length, so that it
+/// returns a handle to the result instead. This is how the import is exposed under `seda_v2`.
+pub fn call_result_handle_import_obj(
+    store: &mut Store,
+    vm_context: &FunctionEnv<VmContext>,
+    import: Function,
+) -> Function {
+    let ty = import.ty(store);
+
+    Function::new_with_env(store, vm_context, ty, move |mut env, args| {
+        import.call(&mut env, args)?;
+
+        let ctx = env.data();
+        let value = std::mem::take(&mut *ctx.call_result_value.write());
+        let handle = ctx
+            .call_results
+            .lock()
+            .insert(value)
+            .map_err(|err| wasmer::RuntimeError::user(Box::new(err)))?;
+
+        Ok(vec![Value::I32(handle as i32)])
+    })
+}
+
+pub fn call_result_handle_length_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn call_result_handle_length(env: FunctionEnvMut<'_, VmContext>, handle: u32) -> Result<u32> {
+        let ctx = env.data();
+        let call_results = ctx.call_results.lock();
+
+        Ok(call_results.get(handle)?.len() as u32)
+    }
+
+    Function::new_typed_with_env(store, vm_context, call_result_handle_length)
+}
+
+pub fn call_result_handle_write_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn call_result_handle_write(
+        env: FunctionEnvMut<'_, VmContext>,
+        handle: u32,
+        result_data_ptr: WasmPtr<u8>,
+        result_data_length: u32,
+    ) -> Result<()> {
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        // Writing releases the handle, even when the write itself fails
+        let call_value = ctx.call_results.lock().take(handle)?;
+        if call_value.len() as u32 != result_data_length {
+            return Err(RuntimeError::InvalidMemoryAccess(
+                "call_result_write: result_data_length does not match the call result length",
+            ));
+        }
+
+        result_data_ptr
+            .slice(&memory, result_data_length)?
+            .write_slice(&call_value)?;
+
+        Ok(())
+    }
+
+    Function::new_typed_with_env(store, vm_context, call_result_handle_write)
+}
@@ -0,0 +1,53 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+
+use crate::{context::VmContext, errors::Result, metering::apply_gas_cost, RuntimeError};
+
+/// Verifies an `Ed25519` signature.
+///
+/// Inputs:
+///     - message (any payload in bytes)
+///     - signature (R and s as a 64-byte concatenated value)
+///     - public_key (32 bytes as a compressed Edwards point)
+///
+/// Output:
+///     - u8 (boolean, 1 for true)
+///
+/// Verification uses the strict rules, which reject small order public keys and malleable signatures.
+pub fn ed25519_verify_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn ed25519_verify(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        message: WasmPtr<u8>,
+        message_length: i64,
+        signature: WasmPtr<u8>,
+        signature_length: i32,
+        public_key: WasmPtr<u8>,
+        public_key_length: i32,
+    ) -> Result<u8> {
+        // Return error if any length is negative
+        if message_length < 0 || signature_length < 0 || public_key_length < 0 {
+            return Err(RuntimeError::Unknown("Negative length provided".to_string()));
+        }
+
+        let message_length = u32::try_from(message_length)
+            .map_err(|_| RuntimeError::Unknown("Length overflow in ed25519_verify".to_string()))?;
+        let total_len = message_length as u64 + signature_length as u64 + public_key_length as u64;
+
+        apply_gas_cost(crate::metering::ExternalCallType::Ed25519Verify(total_len), &mut env)?;
+
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        // Fetch function arguments as Vec<u8>
+        let message = message.slice(&memory, message_length)?.read_to_vec()?;
+        let signature = signature.slice(&memory, signature_length as u32)?.read_to_vec()?;
+        let public_key = public_key.slice(&memory, public_key_length as u32)?.read_to_vec()?;
+
+        let public_key_obj = VerifyingKey::try_from(public_key.as_slice()).map_err(RuntimeError::Ed25519)?;
+        let signature_obj = Signature::from_slice(&signature).map_err(RuntimeError::Ed25519)?;
+
+        Ok(public_key_obj.verify_strict(&message, &signature_obj).is_ok().into())
+    }
+
+    Function::new_typed_with_env(store, vm_context, ed25519_verify)
+}
@@ -0,0 +1,226 @@
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+
+use super::secp256_k1::take_length_prefixed;
+use crate::{context::VmContext, errors::Result, metering::apply_gas_cost, RuntimeError};
+
+/// Length of a proof node, a side byte followed by the 32-byte sibling hash
+const PROOF_NODE_LENGTH: u32 = 33;
+
+/// Length of the two child hashes an inner node hashes
+const INNER_NODE_PREIMAGE_LENGTH: u32 = 64;
+
+const ICS23_LEAF: &str = "ICS-23 leaf in merkle_verify";
+const ICS23_PROOF: &str = "ICS-23 proof in merkle_verify";
+
+/// How leaves and inner nodes of the tree are hashed
+enum MerkleHashAlgorithm {
+    /// `keccak256(leaf)` and `keccak256(left || right)`, as used by EVM chains and OpenZeppelin's `MerkleProof`.
+    /// As there, leaves and inner nodes are not domain separated, so 64-byte leaves are rejected: they could be
+    /// the two child hashes of an inner node.
+    Keccak256,
+    /// `sha256(0x00 || leaf)` and `sha256(0x01 || left || right)`, the RFC 6962 tree hashing used by CometBFT
+    Rfc6962Sha256,
+    /// ICS-23 existence proofs with the `LeafOp` of the IAVL and Tendermint proof specs:
+    /// `sha256(prefix || varint(len(key)) || key || varint(32) || sha256(value))`, and `InnerOp`s
+    /// `sha256(prefix || child || suffix)`. Leaf prefixes have to start with 0x00 and inner prefixes must not.
+    Ics23Sha256,
+}
+
+impl TryFrom<u32> for MerkleHashAlgorithm {
+    type Error = RuntimeError;
+
+    fn try_from(value: u32) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Keccak256),
+            1 => Ok(Self::Rfc6962Sha256),
+            2 => Ok(Self::Ics23Sha256),
+            _ => Err(RuntimeError::Unknown(format!("Unknown merkle hash algorithm: {value}"))),
+        }
+    }
+}
+
+/// A step from a node up to its parent
+enum ProofNode<'a> {
+    /// The sibling hash is on the left
+    Left(&'a [u8]),
+    /// The sibling hash is on the right
+    Right(&'a [u8]),
+    /// An ICS-23 `InnerOp`, the node is hashed between its prefix and suffix
+    Ics23Inner { prefix: &'a [u8], suffix: &'a [u8] },
+}
+
+/// Appends the protobuf varint encoding of `value`, the `VAR_PROTO` length op of ICS-23
+fn push_varint(buffer: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+impl MerkleHashAlgorithm {
+    fn check_lengths(&self, leaf_length: u32, proof_length: u32) -> Result<()> {
+        match self {
+            Self::Keccak256 | Self::Rfc6962Sha256 if proof_length % PROOF_NODE_LENGTH != 0 => Err(
+                RuntimeError::Unknown(format!("Merkle proof length must be a multiple of {PROOF_NODE_LENGTH}")),
+            ),
+            Self::Keccak256 if leaf_length == INNER_NODE_PREIMAGE_LENGTH => Err(RuntimeError::Unknown(format!(
+                "Keccak256 merkle leaves cannot be {INNER_NODE_PREIMAGE_LENGTH} bytes"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The number of input bytes that get hashed besides the proof nodes themselves
+    fn hashed_bytes(&self, leaf_length: u32, proof_length: u32) -> u64 {
+        match self {
+            Self::Keccak256 | Self::Rfc6962Sha256 => leaf_length as u64,
+            // Prefixes and suffixes of the inner ops are hashed as well
+            Self::Ics23Sha256 => leaf_length as u64 + proof_length as u64,
+        }
+    }
+
+    fn parse_proof<'a>(&self, mut proof: &'a [u8]) -> Result<Vec<ProofNode<'a>>> {
+        match self {
+            Self::Keccak256 | Self::Rfc6962Sha256 => proof
+                .chunks_exact(PROOF_NODE_LENGTH as usize)
+                .map(|proof_node| match proof_node[0] {
+                    0 => Ok(ProofNode::Left(&proof_node[1..])),
+                    1 => Ok(ProofNode::Right(&proof_node[1..])),
+                    side => Err(RuntimeError::Unknown(format!("Invalid merkle proof node side: {side}"))),
+                })
+                .collect(),
+            Self::Ics23Sha256 => {
+                let mut proof_nodes = Vec::new();
+                while !proof.is_empty() {
+                    let prefix = take_length_prefixed(&mut proof, ICS23_PROOF)?;
+                    let suffix = take_length_prefixed(&mut proof, ICS23_PROOF)?;
+                    if prefix.first() == Some(&0x00) {
+                        return Err(RuntimeError::Unknown(format!(
+                            "Malformed {ICS23_PROOF}: inner prefixes cannot start with the leaf prefix 0x00"
+                        )));
+                    }
+
+                    proof_nodes.push(ProofNode::Ics23Inner { prefix, suffix });
+                }
+
+                Ok(proof_nodes)
+            }
+        }
+    }
+
+    fn hash_leaf(&self, leaf: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Keccak256 => Ok(Keccak256::digest(leaf).to_vec()),
+            Self::Rfc6962Sha256 => Ok(Sha256::new_with_prefix([0x00]).chain_update(leaf).finalize().to_vec()),
+            Self::Ics23Sha256 => {
+                let mut leaf = leaf;
+                let key = take_length_prefixed(&mut leaf, ICS23_LEAF)?;
+                let value = take_length_prefixed(&mut leaf, ICS23_LEAF)?;
+                let prefix = take_length_prefixed(&mut leaf, ICS23_LEAF)?;
+                if !leaf.is_empty() || key.is_empty() || value.is_empty() || prefix.first() != Some(&0x00) {
+                    return Err(RuntimeError::Unknown(format!("Malformed {ICS23_LEAF}")));
+                }
+
+                let mut preimage = prefix.to_vec();
+                push_varint(&mut preimage, key.len());
+                preimage.extend_from_slice(key);
+                let value_hash = Sha256::digest(value);
+                push_varint(&mut preimage, value_hash.len());
+                preimage.extend_from_slice(&value_hash);
+
+                Ok(Sha256::digest(preimage).to_vec())
+            }
+        }
+    }
+
+    fn hash_parent(&self, node: &[u8], proof_node: &ProofNode) -> Vec<u8> {
+        let (prefix, left, right): (&[u8], &[u8], &[u8]) = match (self, proof_node) {
+            (_, ProofNode::Ics23Inner { prefix, suffix }) => (*prefix, node, *suffix),
+            (Self::Rfc6962Sha256, ProofNode::Left(sibling)) => (&[0x01], *sibling, node),
+            (Self::Rfc6962Sha256, ProofNode::Right(sibling)) => (&[0x01], node, *sibling),
+            (_, ProofNode::Left(sibling)) => (&[], *sibling, node),
+            (_, ProofNode::Right(sibling)) => (&[], node, *sibling),
+        };
+
+        match self {
+            Self::Keccak256 => Keccak256::new_with_prefix(prefix)
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .to_vec(),
+            Self::Rfc6962Sha256 | Self::Ics23Sha256 => Sha256::new_with_prefix(prefix)
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .to_vec(),
+        }
+    }
+}
+
+/// Verifies that a leaf is included in a Merkle tree.
+///
+/// Inputs:
+///     - root (32 bytes)
+///     - leaf (any payload in bytes, hashed by the host; for ICS-23 the packed key, value and `LeafOp` prefix, each
+///       prefixed by its u32 little endian length)
+///     - proof (packed nodes from the leaf up, each a side byte (0 when the sibling is on the left, 1 when it is on the
+///       right) followed by the 32-byte sibling hash; for ICS-23 the packed prefix and suffix of every `InnerOp`, each
+///       prefixed by its u32 little endian length)
+///     - algorithm (u32, 0 for keccak256, 1 for RFC 6962 sha256 and 2 for ICS-23 sha256)
+///
+/// Output:
+///     - u8 (boolean, 1 for true)
+pub fn merkle_verify_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    #[allow(clippy::too_many_arguments)]
+    fn merkle_verify(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        root: WasmPtr<u8>,
+        root_length: u32,
+        leaf: WasmPtr<u8>,
+        leaf_length: u32,
+        proof: WasmPtr<u8>,
+        proof_length: u32,
+        algorithm: u32,
+    ) -> Result<u8> {
+        if root_length != 32 {
+            return Err(RuntimeError::Unknown("Merkle root must be 32 bytes".to_string()));
+        }
+
+        let algorithm = MerkleHashAlgorithm::try_from(algorithm)?;
+        algorithm.check_lengths(leaf_length, proof_length)?;
+
+        let (root, leaf, proof) = {
+            let ctx = env.data();
+            let memory = ctx.memory_view(&env);
+
+            (
+                root.slice(&memory, root_length)?.read_to_vec()?,
+                leaf.slice(&memory, leaf_length)?.read_to_vec()?,
+                proof.slice(&memory, proof_length)?.read_to_vec()?,
+            )
+        };
+
+        // ICS-23 inner ops have a variable length, so the nodes are only known once the proof is read
+        let proof_nodes = algorithm.parse_proof(&proof)?;
+        apply_gas_cost(
+            crate::metering::ExternalCallType::MerkleVerify(
+                algorithm.hashed_bytes(leaf_length, proof_length),
+                proof_nodes.len() as u64,
+            ),
+            &mut env,
+        )?;
+
+        let node = proof_nodes
+            .iter()
+            .fold(algorithm.hash_leaf(&leaf)?, |node, proof_node| {
+                algorithm.hash_parent(&node, proof_node)
+            });
+
+        Ok((node == root).into())
+    }
+
+    Function::new_typed_with_env(store, vm_context, merkle_verify)
+}
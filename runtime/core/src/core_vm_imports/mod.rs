@@ -1,11 +1,152 @@
-use wasmer::{imports, FunctionEnv, Imports, Store, WasmPtr};
+use lazy_static::lazy_static;
+use wasmer::{Exports, Extern, Function, FunctionEnv, Imports, Store};
 
-use crate::context::VmContext;
+use crate::{abi::AbiVersion, context::VmContext, vm_imports::FETCH_IMPORTS, SAFE_WASI_IMPORTS};
 
+mod blake2b_256;
+mod bn254;
 mod call_result;
+mod ed25519;
 mod execution_result;
 mod keccak256;
+mod merkle;
+mod ripemd160;
 mod secp256_k1;
+mod sha256;
+
+lazy_static! {
+    /// Core mode only gets the pure host functions, there is no way to reach the network
+    pub static ref SAFE_CORE_IMPORTS: Vec<String> = {
+        [
+            "execution_result",
+            "call_result_write",
+            "secp256k1_verify",
+            "secp256k1_recover",
+            "secp256k1_verify_batch",
+            "bn254_verify",
+            "ed25519_verify",
+            "keccak256",
+            "sha256",
+            "blake2b_256",
+            "ripemd160",
+            "merkle_verify",
+        ]
+        .iter()
+        .map(|import| import.to_string())
+        .chain(SAFE_WASI_IMPORTS.to_vec())
+        .collect()
+    };
+}
+
+/// How an import hands its output back to the program
+#[derive(PartialEq, Eq)]
+enum ImportOutput {
+    /// The output is the return value
+    ReturnValue,
+    /// The output is left in `call_result_value` and the import returns its length
+    CallResult,
+}
+
+type ImportObj = fn(&mut Store, &FunctionEnv<VmContext>) -> Function;
+
+/// The `seda_v1` core imports together with how they hand back their output
+const CORE_IMPORTS: [(&str, ImportObj, ImportOutput); 13] = [
+    (
+        "blake2b_256",
+        blake2b_256::blake2b_256_import_obj,
+        ImportOutput::CallResult,
+    ),
+    (
+        "bn254_verify",
+        bn254::bn254_verify_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    (
+        "call_result_length",
+        call_result::call_result_value_length_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    (
+        "call_result_write",
+        call_result::call_result_value_write_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    (
+        "ed25519_verify",
+        ed25519::ed25519_verify_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    (
+        "execution_result",
+        execution_result::execution_result_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    ("keccak256", keccak256::keccak256_import_obj, ImportOutput::CallResult),
+    (
+        "merkle_verify",
+        merkle::merkle_verify_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    ("ripemd160", ripemd160::ripemd160_import_obj, ImportOutput::CallResult),
+    (
+        "secp256k1_recover",
+        secp256_k1::secp256k1_recover_import_obj,
+        ImportOutput::CallResult,
+    ),
+    (
+        "secp256k1_verify",
+        secp256_k1::secp256k1_verify_import_obj,
+        ImportOutput::ReturnValue,
+    ),
+    (
+        "secp256k1_verify_batch",
+        secp256_k1::secp256k1_verify_batch_import_obj,
+        ImportOutput::CallResult,
+    ),
+    ("sha256", sha256::sha256_import_obj, ImportOutput::CallResult),
+];
+
+/// Whether the import leaves its result in `call_result_value` and returns its length,
+/// the [`FETCH_IMPORTS`] all do.
+fn is_call_result_import(name: &str) -> bool {
+    FETCH_IMPORTS.contains(&name)
+        || CORE_IMPORTS
+            .iter()
+            .any(|(import, _, output)| *import == name && *output == ImportOutput::CallResult)
+}
+
+/// Derives the `seda_v2` exports from the allowed `seda_v1` exports. Imports with a call result return a handle
+/// instead of a length, and `call_result_length`/`call_result_write` take that handle, so several results can be
+/// pending at the same time.
+pub fn create_seda_v2_exports(
+    store: &mut Store,
+    vm_context: &FunctionEnv<VmContext>,
+    seda_v1_exports: &Exports,
+) -> Exports {
+    let mut seda_v2_exports = Exports::new();
+
+    for (name, export) in seda_v1_exports.iter() {
+        let export = match export {
+            Extern::Function(function) if is_call_result_import(name) => {
+                call_result::call_result_handle_import_obj(store, vm_context, function.clone()).into()
+            }
+            _ => export.clone(),
+        };
+
+        seda_v2_exports.insert(name, export);
+    }
+
+    seda_v2_exports.insert(
+        "call_result_length",
+        call_result::call_result_handle_length_import_obj(store, vm_context),
+    );
+    seda_v2_exports.insert(
+        "call_result_write",
+        call_result::call_result_handle_write_import_obj(store, vm_context),
+    );
+
+    seda_v2_exports
+}
 
 #[macro_export]
 macro_rules! generic_polyfill_import_obj {
@@ -23,24 +164,10 @@ macro_rules! generic_polyfill_import_obj {
 }
 
 pub fn create_custom_core_imports(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Imports {
-    let core_imports = imports! {
-        "seda_v1" => {
-            "bn254_verify" => generic_polyfill_import_obj!(
-                "bn254_verify", u8,
-                _message: WasmPtr<u8>,
-                _message_length: i64,
-                _signature: WasmPtr<u8>,
-                _signature_length: i64,
-                _public_key: WasmPtr<u8>,
-                _public_key_length: i64
-            )(store, vm_context),
-            "call_result_length" => call_result::call_result_value_length_import_obj(store, vm_context),
-            "call_result_write" => call_result::call_result_value_write_import_obj(store, vm_context),
-            "execution_result" => execution_result::execution_result_import_obj(store, vm_context),
-            "keccak256" => keccak256::keccak256_import_obj(store, vm_context),
-            "secp256k1_verify" => secp256_k1::secp256k1_verify_import_obj(store, vm_context)
-        },
-    };
+    let mut core_imports = Imports::new();
+    for (name, import_obj, _) in CORE_IMPORTS {
+        core_imports.define(AbiVersion::V1.namespace(), name, import_obj(store, vm_context));
+    }
 
     core_imports
 }
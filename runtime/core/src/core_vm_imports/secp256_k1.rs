@@ -1,9 +1,19 @@
-use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use k256::ecdsa::{signature::hazmat::PrehashVerifier, RecoveryId, Signature, VerifyingKey};
 use sha3::{Digest, Keccak256};
 use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
 
 use crate::{context::VmContext, errors::Result, metering::apply_gas_cost, RuntimeError};
 
+/// `Secp256k1` verification (using Keccak256 hashing)
+fn verify_signature(message: &[u8], signature: &[u8], public_key: &[u8]) -> Result<bool> {
+    let public_key_obj = VerifyingKey::from_sec1_bytes(public_key)?;
+    let signature_obj = Signature::from_slice(signature)?;
+    let hashed_message = Keccak256::digest(message);
+
+    // Verify the signature using prehashed message
+    Ok(public_key_obj.verify_prehash(&hashed_message, &signature_obj).is_ok())
+}
+
 /// Verifies a `Secp256k1` ECDSA signature.
 ///
 /// Inputs:
@@ -48,17 +58,171 @@ pub fn secp256k1_verify_import_obj(store: &mut Store, vm_context: &FunctionEnv<V
         let signature = signature.slice(&memory, signature_length as u32)?.read_to_vec()?;
         let public_key = public_key.slice(&memory, public_key_length as u32)?.read_to_vec()?;
 
-        // `Secp256k1` verification (using Keccak256 hashing)
-        let public_key_obj = VerifyingKey::from_sec1_bytes(&public_key)?;
-        let signature_obj = Signature::from_slice(&signature)?;
+        Ok(verify_signature(&message, &signature, &public_key)?.into())
+    }
+
+    Function::new_typed_with_env(store, vm_context, secp256k1_verify)
+}
+
+/// Recovers the public key of a `Secp256k1` ECDSA signature, like `ecrecover` on EVM chains.
+///
+/// Inputs:
+///     - message (any payload in bytes)
+///     - signature (r, s and the recovery id as a 65-byte concatenated value, v may be 0/1 or 27/28)
+///     - reject_high_s (u32, 1 to only accept signatures with a normalized (low) s value)
+///
+/// Output:
+///     - u32 (length of the call result, 0 if no key could be recovered)
+///
+/// On success the call result holds the compressed (33 bytes) followed by the uncompressed (65 bytes) public key.
+pub fn secp256k1_recover_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn secp256k1_recover(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        message: WasmPtr<u8>,
+        message_length: i64,
+        signature: WasmPtr<u8>,
+        signature_length: i32,
+        reject_high_s: u32,
+    ) -> Result<u32> {
+        // Return error if any length is negative
+        if message_length < 0 || signature_length < 0 {
+            return Err(RuntimeError::Unknown("Negative length provided".to_string()));
+        }
+
+        if signature_length != 65 {
+            return Err(RuntimeError::Unknown(
+                "Signature for secp256k1_recover must be 65 bytes".to_string(),
+            ));
+        }
+
+        let message_length = u32::try_from(message_length)
+            .map_err(|_| RuntimeError::Unknown("Length overflow in secp256k1_recover".to_string()))?;
+
+        apply_gas_cost(
+            crate::metering::ExternalCallType::Secp256k1Recover(message_length as u64),
+            &mut env,
+        )?;
+
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        let message = message.slice(&memory, message_length)?.read_to_vec()?;
+        let signature = signature.slice(&memory, signature_length as u32)?.read_to_vec()?;
+
+        let signature_obj = Signature::from_slice(&signature[..64])?;
+        let recovery_byte = match signature[64] {
+            byte @ (27 | 28) => byte - 27,
+            byte => byte,
+        };
+        let recovery_id = RecoveryId::from_byte(recovery_byte)
+            .ok_or_else(|| RuntimeError::Unknown(format!("Invalid recovery id: {}", signature[64])))?;
+
+        // A high s value is the negation of the low one, which flips the parity of the y coordinate
+        let (signature_obj, recovery_id) = match signature_obj.normalize_s() {
+            Some(_) if reject_high_s != 0 => {
+                *ctx.call_result_value.write() = Vec::new();
+                return Ok(0);
+            }
+            Some(normalized) => (
+                normalized,
+                RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced()),
+            ),
+            None => (signature_obj, recovery_id),
+        };
+
         let hashed_message = Keccak256::digest(&message);
+        let mut call_value = ctx.call_result_value.write();
+        *call_value = match VerifyingKey::recover_from_prehash(&hashed_message, &signature_obj, recovery_id) {
+            Ok(public_key) => [
+                public_key.to_encoded_point(true).as_bytes(),
+                public_key.to_encoded_point(false).as_bytes(),
+            ]
+            .concat(),
+            Err(_) => Vec::new(),
+        };
 
-        // Verify the signature using prehashed message
-        Ok(public_key_obj
-            .verify_prehash(&hashed_message, &signature_obj)
-            .is_ok()
-            .into())
+        Ok(call_value.len() as u32)
     }
 
-    Function::new_typed_with_env(store, vm_context, secp256k1_verify)
+    Function::new_typed_with_env(store, vm_context, secp256k1_recover)
+}
+
+/// Takes the next value, prefixed by its `u32` little endian length, off packed values.
+/// `what` names the packed values in the error.
+pub(super) fn take_length_prefixed<'a>(entries: &mut &'a [u8], what: &str) -> Result<&'a [u8]> {
+    let malformed = || RuntimeError::Unknown(format!("Malformed {what}"));
+
+    let (length, rest) = entries.split_first_chunk::<4>().ok_or_else(malformed)?;
+    let length = u32::from_le_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(malformed());
+    }
+
+    let (value, rest) = rest.split_at(length);
+    *entries = rest;
+
+    Ok(value)
+}
+
+const BATCH_ENTRIES: &str = "entries in secp256k1_verify_batch";
+
+/// Verifies a batch of `Secp256k1` ECDSA signatures in a single call.
+///
+/// Inputs:
+///     - entries (packed (message, signature, public_key) tuples, every value prefixed by its u32 little endian length,
+///       with the same formats as `secp256k1_verify`)
+///     - entries_count (u32, the number of tuples, used to charge the gas before anything is read)
+///
+/// Output:
+///     - u32 (length of the call result)
+///
+/// The call result is a bitmap with a bit per tuple, least significant bit first, set when the signature is valid.
+/// A malformed signature or public key only clears its bit, a malformed packing fails the call.
+pub fn secp256k1_verify_batch_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn secp256k1_verify_batch(
+        mut env: FunctionEnvMut<'_, VmContext>,
+        entries: WasmPtr<u8>,
+        entries_length: u32,
+        entries_count: u32,
+    ) -> Result<u32> {
+        // Every tuple holds at least its three length prefixes
+        if entries_count > entries_length / 12 {
+            return Err(RuntimeError::Unknown(format!("Malformed {BATCH_ENTRIES}")));
+        }
+
+        apply_gas_cost(
+            crate::metering::ExternalCallType::Secp256k1VerifyBatch(entries_count as u64, entries_length as u64),
+            &mut env,
+        )?;
+
+        let ctx = env.data();
+        let memory = ctx.memory_view(&env);
+
+        let entries = entries.slice(&memory, entries_length)?.read_to_vec()?;
+
+        let mut remaining = entries.as_slice();
+        let mut bitmap = vec![0u8; (entries_count as usize).div_ceil(8)];
+        for index in 0..entries_count as usize {
+            let message = take_length_prefixed(&mut remaining, BATCH_ENTRIES)?;
+            let signature = take_length_prefixed(&mut remaining, BATCH_ENTRIES)?;
+            let public_key = take_length_prefixed(&mut remaining, BATCH_ENTRIES)?;
+
+            if verify_signature(message, signature, public_key).unwrap_or(false) {
+                bitmap[index / 8] |= 1 << (index % 8);
+            }
+        }
+
+        if !remaining.is_empty() {
+            return Err(RuntimeError::Unknown(format!(
+                "secp256k1_verify_batch got more than {entries_count} entries"
+            )));
+        }
+
+        let mut call_value = ctx.call_result_value.write();
+        *call_value = bitmap;
+
+        Ok(call_value.len() as u32)
+    }
+
+    Function::new_typed_with_env(store, vm_context, secp256k1_verify_batch)
 }
@@ -0,0 +1,48 @@
+use wasmer::{Function, FunctionEnv, FunctionEnvMut, Store, WasmPtr};
+
+use super::fetch_result_to_promise;
+use crate::{
+    errors::{Result, VmHostError},
+    metering::apply_gas_cost,
+    RuntimeError,
+    VmContext,
+};
+
+/// Hands the fetch action over to the host supplied [`HttpFetcher`](crate::HttpFetcher)
+/// and stores the resulting promise for the `call_result_write` import.
+pub fn http_fetch_import_obj(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Function {
+    fn http_fetch(mut env: FunctionEnvMut<'_, VmContext>, action_ptr: WasmPtr<u8>, action_length: i32) -> Result<u32> {
+        // Return error if length is negative
+        if action_length < 0 {
+            return Err(RuntimeError::Unknown("Negative length provided".to_string()));
+        }
+
+        apply_gas_cost(
+            crate::metering::ExternalCallType::HttpFetchRequest(action_length as u64),
+            &mut env,
+        )?;
+
+        let len = {
+            let ctx = env.data();
+            let memory = ctx.memory_view(&env);
+            let action = action_ptr.slice(&memory, action_length as u32)?.read_to_vec()?;
+
+            let http_fetcher = ctx.http_fetcher.as_ref().ok_or(VmHostError::HttpFetcherNotSet)?;
+            let result = fetch_result_to_promise(http_fetcher.http_fetch(&action))?;
+
+            let mut call_value = ctx.call_result_value.write();
+            *call_value = serde_json::to_vec(&result).map_err(VmHostError::from)?;
+
+            call_value.len()
+        };
+
+        apply_gas_cost(
+            crate::metering::ExternalCallType::HttpFetchResponse(len as u64),
+            &mut env,
+        )?;
+
+        Ok(len as u32)
+    }
+
+    Function::new_typed_with_env(store, vm_context, http_fetch)
+}
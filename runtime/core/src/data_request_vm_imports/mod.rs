@@ -0,0 +1,33 @@
+use lazy_static::lazy_static;
+use seda_sdk_rs::promise::PromiseStatus;
+use wasmer::{FunctionEnv, Imports, Store};
+
+use crate::{
+    errors::{Result, VmHostError},
+    http_fetcher::HttpFetchResult,
+    vm_imports::{create_imports_with_fetch, safe_imports_with_fetch},
+    VmContext,
+};
+
+mod http_fetch;
+mod proxy_http_fetch;
+
+lazy_static! {
+    /// The core imports plus the ones that can reach the network
+    pub static ref SAFE_DATA_REQUEST_IMPORTS: Vec<String> = safe_imports_with_fetch();
+}
+
+/// Converts the outcome of a host fetch into the promise the SDK expects in the call result.
+fn fetch_result_to_promise(result: HttpFetchResult) -> Result<PromiseStatus> {
+    Ok(match result {
+        Ok(response) => PromiseStatus::Fulfilled(Some(serde_json::to_vec(&response).map_err(VmHostError::from)?)),
+        Err(response) => PromiseStatus::Rejected(serde_json::to_vec(&response).map_err(VmHostError::from)?),
+    })
+}
+
+pub fn create_custom_data_request_imports(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Imports {
+    let http_fetch = http_fetch::http_fetch_import_obj(store, vm_context);
+    let proxy_http_fetch = proxy_http_fetch::proxy_http_fetch_import_obj(store, vm_context);
+
+    create_imports_with_fetch(store, vm_context, http_fetch, proxy_http_fetch)
+}
@@ -11,8 +11,26 @@ pub enum VmHostError {
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
+    #[error("No HTTP fetcher was provided for data request mode")]
+    HttpFetcherNotSet,
+
     #[error("Invalid WASM cache path, it exists but is not a directory: {0}")]
     InvalidCachePath(String),
+
+    #[error("Invalid WASM cache ID: {0}")]
+    InvalidWasmCacheId(String),
+
+    #[error("WASM with ID {0} is not cached")]
+    WasmNotCached(String),
+
+    #[error("Invalid gas schedule: {0}")]
+    InvalidGasSchedule(serde_json::Error),
+
+    #[error("Invalid gas schedule registry: {0}")]
+    InvalidGasScheduleRegistry(String),
+
+    #[error("Unknown gas schedule version: {0}")]
+    UnknownGasScheduleVersion(String),
 }
 
 #[derive(Debug, Error)]
@@ -59,12 +77,22 @@ pub enum RuntimeError {
     #[error(transparent)]
     WasmDeserializeError(#[from] wasmer::DeserializeError),
 
+    #[error("Invalid WASM binary: {0}")]
+    WasmParseError(String),
+
     #[error(transparent)]
     Utf8(#[from] FromUtf8Error),
 
     #[error(transparent)]
     Ecdsa(#[from] k256::ecdsa::Error),
 
+    #[error("BN254 Error: {0}")]
+    Bn254(#[from] bn254::Error),
+
+    /// Same type as the ECDSA error, so it is not converted with `?`
+    #[error("Ed25519 Error: {0}")]
+    Ed25519(ed25519_dalek::SignatureError),
+
     #[error("Out of gas")]
     OutOfGas,
 
@@ -78,4 +106,11 @@ pub enum RuntimeError {
     InvalidMemoryAccess(&'static str),
 }
 
+// wasmer builds wasmparser without its std feature, so its error does not implement `std::error::Error`
+impl From<wasmer::wasmparser::BinaryReaderError> for RuntimeError {
+    fn from(err: wasmer::wasmparser::BinaryReaderError) -> Self {
+        RuntimeError::WasmParseError(err.to_string())
+    }
+}
+
 pub type Result<T, E = RuntimeError> = core::result::Result<T, E>;
@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::{errors::VmHostError, metering::TERA_GAS};
+
+const GAS_MULTIPLIER: u64 = 150;
+
+/// Prices of everything the VM charges gas for. Chain governance can change them without shipping a new library,
+/// the defaults are the prices the VM used before schedules were configurable.
+///
+/// Every field is required when deserializing, so a schedule never silently picks up a default for a price it
+/// forgot to set. The only exception are prices that were added later, schedules written before them did not charge
+/// for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
+pub struct GasSchedule {
+    /// Cost of a regular WASM operator
+    pub per_operation:                        u64,
+    /// Multiplier on `per_operation` for operators that end a metered block (branches, calls, etc.)
+    pub accounting_multiplier:                u64,
+    pub memory_grow_base:                     u64,
+    /// Cost for every page `memory.grow` requests, charged at runtime
+    #[serde(default)]
+    pub memory_grow_per_page:                 u64,
+    /// Cost for every byte `memory.fill`, `memory.copy` and `memory.init` write, charged at runtime
+    #[serde(default)]
+    pub memory_bulk_per_byte:                 u64,
+    /// Cost for every element the `table.*` bulk operators and `table.grow` write, charged at runtime
+    #[serde(default)]
+    pub table_bulk_per_element:               u64,
+    /// Cost for reading and writing a byte
+    pub per_byte:                             u64,
+    pub per_byte_execution_result:            u64,
+    pub startup:                              u64,
+    /// Cost for every byte of the environment variables, charged on startup
+    #[serde(default)]
+    pub startup_per_env_byte:                 u64,
+    /// Cost for every byte of the WASM binary, charged on startup
+    #[serde(default)]
+    pub startup_per_wasm_byte:                u64,
+    /// Cost for every page of memory the module starts with, charged on startup
+    #[serde(default)]
+    pub startup_per_memory_page:              u64,
+    /// Cost for every byte the data segments copy into memory, charged on startup
+    #[serde(default)]
+    pub startup_per_data_segment_byte:        u64,
+    pub http_fetch_base:                      u64,
+    pub proxy_http_fetch_base:                u64,
+    pub bn254_verify_base:                    u64,
+    pub secp256k1_base:                       u64,
+    pub secp256k1_recover_base:               u64,
+    pub secp256k1_verify_batch_base:          u64,
+    pub secp256k1_verify_batch_per_signature: u64,
+    pub keccak256_base:                       u64,
+    pub sha256_base:                          u64,
+    pub blake2b_256_base:                     u64,
+    pub ripemd160_base:                       u64,
+    pub merkle_verify_base:                   u64,
+    pub merkle_verify_per_node:               u64,
+    pub ed25519_verify_base:                  u64,
+    pub args_get_base:                        u64,
+    pub args_sizes_get_base:                  u64,
+    pub environ_get_base:                     u64,
+    pub environ_sizes_get_base:               u64,
+    pub fd_write_base:                        u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            per_operation:                        125 * GAS_MULTIPLIER,
+            accounting_multiplier:                3_000,
+            memory_grow_base:                     1_000_000,
+            // Growing memory used to cost the same no matter how many pages were requested.
+            memory_grow_per_page:                 0,
+            // Bulk operators used to cost a single operation no matter how much they wrote.
+            memory_bulk_per_byte:                 0,
+            table_bulk_per_element:               0,
+            per_byte:                             10_000,
+            per_byte_execution_result:            10_000_000,
+            startup:                              TERA_GAS * 5,
+            // Only the args used to count towards the startup cost.
+            startup_per_env_byte:                 0,
+            startup_per_wasm_byte:                0,
+            startup_per_memory_page:              0,
+            startup_per_data_segment_byte:        0,
+            // Makes it so you can do roughly 30 http requests with the current gas calculations.
+            http_fetch_base:                      TERA_GAS * 5,
+            // Makes it so you can do roughly 25 proxy http requests with the current gas calculations.
+            proxy_http_fetch_base:                TERA_GAS * 7,
+            bn254_verify_base:                    TERA_GAS,
+            secp256k1_base:                       TERA_GAS,
+            // Recovery does a little more work than verification, it has to decompress the R point first.
+            secp256k1_recover_base:               TERA_GAS + TERA_GAS / 4,
+            // A batch crosses the host boundary once, each signature costs less than a separate verify call.
+            secp256k1_verify_batch_base:          TERA_GAS,
+            secp256k1_verify_batch_per_signature: TERA_GAS + TERA_GAS / 4,
+            keccak256_base:                       TERA_GAS,
+            sha256_base:                          TERA_GAS,
+            blake2b_256_base:                     TERA_GAS,
+            ripemd160_base:                       TERA_GAS,
+            merkle_verify_base:                   TERA_GAS,
+            // Hashing a node is a small fixed size hash, well below the cost of a separate hash import call.
+            merkle_verify_per_node:               TERA_GAS / 10,
+            ed25519_verify_base:                  TERA_GAS,
+            args_get_base:                        TERA_GAS,
+            args_sizes_get_base:                  TERA_GAS,
+            environ_get_base:                     TERA_GAS,
+            environ_sizes_get_base:               TERA_GAS,
+            fd_write_base:                        TERA_GAS,
+        }
+    }
+}
+
+impl GasSchedule {
+    pub fn from_json(json: &[u8]) -> Result<Self, VmHostError> {
+        serde_json::from_slice(json).map_err(VmHostError::InvalidGasSchedule)
+    }
+
+    /// JSON object with the keys sorted and without any whitespace, the same schedule always gives the same bytes
+    pub fn canonical_json(&self) -> Vec<u8> {
+        let value = serde_json::to_value(self).expect("a gas schedule only holds integers");
+        // Sort explicitly instead of relying on the map type serde_json was built with
+        let fields: BTreeMap<&String, &serde_json::Value> = value
+            .as_object()
+            .expect("a gas schedule serializes to an object")
+            .iter()
+            .collect();
+
+        serde_json::to_vec(&fields).expect("a gas schedule only holds integers")
+    }
+
+    /// Hex encoded keccak256 of the canonical JSON
+    pub fn hash(&self) -> String {
+        hex::encode(Keccak256::digest(self.canonical_json()))
+    }
+
+    /// The default prices plus the prices for what is charged by size at runtime and on startup.
+    ///
+    /// These prices are frozen: once governance registers v2, replaying its blocks depends on them.
+    /// Any later price change goes into a new version with its own name.
+    pub fn v2() -> Self {
+        let default = Self::default();
+        // Bulk operators write many bytes per cycle, a byte costs far less than an operator.
+        let memory_bulk_per_byte = GAS_MULTIPLIER;
+        // A 64 KiB page of memory is zeroed like a `memory.fill` of the whole page.
+        let memory_per_page = 65_536 * memory_bulk_per_byte;
+
+        Self {
+            memory_grow_per_page: memory_per_page,
+            memory_bulk_per_byte,
+            // A table element is a reference, priced like the 8 bytes it takes up.
+            table_bulk_per_element: 8 * memory_bulk_per_byte,
+            // Env variables are read like the args, which were already charged per byte.
+            startup_per_env_byte: default.per_byte,
+            // Validating and loading the module goes over every byte more than once.
+            startup_per_wasm_byte: 10 * default.per_byte,
+            startup_per_memory_page: memory_per_page,
+            // Data segments are copied into memory like a `memory.init`.
+            startup_per_data_segment_byte: memory_bulk_per_byte,
+            ..default
+        }
+    }
+}
+
+/// Name of the version the default gas schedule is registered under
+pub const DEFAULT_GAS_SCHEDULE_VERSION: &str = "v1";
+
+/// Name of the version [`GasSchedule::v2`] is registered under
+pub const GAS_SCHEDULE_V2_VERSION: &str = "v2";
+
+/// A named gas schedule, it applies from its activation height until the next version activates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
+pub struct GasScheduleVersion {
+    pub name:              String,
+    pub activation_height: u64,
+    pub gas_schedule:      GasSchedule,
+}
+
+impl Default for GasScheduleVersion {
+    fn default() -> Self {
+        Self {
+            name:              DEFAULT_GAS_SCHEDULE_VERSION.to_string(),
+            activation_height: 0,
+            gas_schedule:      GasSchedule::default(),
+        }
+    }
+}
+
+impl GasScheduleVersion {
+    /// [`GasSchedule::v2`] is opt-in, governance registers it with the height it activates at
+    pub fn v2(activation_height: u64) -> Self {
+        Self {
+            name: GAS_SCHEDULE_V2_VERSION.to_string(),
+            activation_height,
+            gas_schedule: GasSchedule::v2(),
+        }
+    }
+}
+
+/// How a request picks its gas schedule version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GasScheduleSelector {
+    /// The version that was active at the given block height
+    Height(u64),
+    /// The version with the given name
+    Version(String),
+}
+
+/// Every gas schedule version the chain ever activated. Replaying a block has to charge the prices of its height, so
+/// versions are only ever added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasScheduleRegistry {
+    /// Sorted by activation height, the first one activates at height 0
+    versions: Vec<GasScheduleVersion>,
+}
+
+impl Default for GasScheduleRegistry {
+    fn default() -> Self {
+        Self {
+            versions: vec![GasScheduleVersion::default()],
+        }
+    }
+}
+
+impl GasScheduleRegistry {
+    pub fn new(mut versions: Vec<GasScheduleVersion>) -> Result<Self, VmHostError> {
+        versions.sort_by_key(|version| version.activation_height);
+
+        if versions.first().is_none_or(|version| version.activation_height != 0) {
+            return Err(VmHostError::InvalidGasScheduleRegistry(
+                "no version activates at height 0".to_string(),
+            ));
+        }
+
+        for (index, version) in versions.iter().enumerate() {
+            let earlier = &versions[..index];
+            if earlier.iter().any(|other| other.name == version.name) {
+                return Err(VmHostError::InvalidGasScheduleRegistry(format!(
+                    "version {} is registered more than once",
+                    version.name
+                )));
+            }
+
+            if earlier
+                .iter()
+                .any(|other| other.activation_height == version.activation_height)
+            {
+                return Err(VmHostError::InvalidGasScheduleRegistry(format!(
+                    "more than one version activates at height {}",
+                    version.activation_height
+                )));
+            }
+        }
+
+        Ok(Self { versions })
+    }
+
+    /// Parses a JSON list of [`GasScheduleVersion`]s
+    pub fn from_json(json: &[u8]) -> Result<Self, VmHostError> {
+        Self::new(serde_json::from_slice(json).map_err(VmHostError::InvalidGasSchedule)?)
+    }
+
+    /// All versions, ordered by activation height
+    pub fn versions(&self) -> &[GasScheduleVersion] {
+        &self.versions
+    }
+
+    /// The version that activated last, which is what new executions run with
+    pub fn latest(&self) -> &GasScheduleVersion {
+        self.versions.last().expect("a registry has at least one version")
+    }
+
+    pub fn select(&self, selector: &GasScheduleSelector) -> Result<&GasScheduleVersion, VmHostError> {
+        match selector {
+            GasScheduleSelector::Height(height) => Ok(self
+                .versions
+                .iter()
+                .rev()
+                .find(|version| version.activation_height <= *height)
+                .expect("the first version activates at height 0")),
+            GasScheduleSelector::Version(name) => self
+                .versions
+                .iter()
+                .find(|version| &version.name == name)
+                .ok_or_else(|| VmHostError::UnknownGasScheduleVersion(name.clone())),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use seda_sdk_rs::HttpFetchResponse;
+
+/// The outcome of a fetch performed by the host.
+/// `Ok` is handed to the WASM binary as a fulfilled promise, `Err` as a rejected one.
+pub type HttpFetchResult = core::result::Result<HttpFetchResponse, HttpFetchResponse>;
+
+/// Performs the HTTP requests of a data request execution on behalf of the WASM binary.
+///
+/// The VM only meters and marshals the calls, the actual networking is left to the host.
+/// Both methods receive the JSON encoded action exactly as the SDK wrote it to memory.
+pub trait HttpFetcher: Send + Sync {
+    fn http_fetch(&self, action: &[u8]) -> HttpFetchResult;
+
+    fn proxy_http_fetch(&self, action: &[u8]) -> HttpFetchResult;
+}
+
+#[cfg(feature = "test-utils")]
+mod mock {
+    use parking_lot::Mutex;
+    use seda_sdk_rs::HttpFetchResponse;
+
+    use super::{HttpFetchResult, HttpFetcher};
+
+    /// An in-process fetcher that answers every request with the same response
+    /// and keeps track of the actions it received.
+    pub struct MockHttpFetcher {
+        response:    HttpFetchResponse,
+        http_calls:  Mutex<Vec<Vec<u8>>>,
+        proxy_calls: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl MockHttpFetcher {
+        pub fn new(response: HttpFetchResponse) -> Self {
+            Self {
+                response,
+                http_calls: Mutex::new(Vec::new()),
+                proxy_calls: Mutex::new(Vec::new()),
+            }
+        }
+
+        pub fn http_calls(&self) -> Vec<Vec<u8>> {
+            self.http_calls.lock().clone()
+        }
+
+        pub fn proxy_calls(&self) -> Vec<Vec<u8>> {
+            self.proxy_calls.lock().clone()
+        }
+
+        fn respond(&self) -> HttpFetchResult {
+            // Mirror the overlay node, anything outside the 2xx range rejects the promise
+            if (200..300).contains(&self.response.status) {
+                Ok(self.response.clone())
+            } else {
+                Err(self.response.clone())
+            }
+        }
+    }
+
+    impl HttpFetcher for MockHttpFetcher {
+        fn http_fetch(&self, action: &[u8]) -> HttpFetchResult {
+            self.http_calls.lock().push(action.to_vec());
+            self.respond()
+        }
+
+        fn proxy_http_fetch(&self, action: &[u8]) -> HttpFetchResult {
+            self.proxy_calls.lock().push(action.to_vec());
+            self.respond()
+        }
+    }
+}
+
+#[cfg(feature = "test-utils")]
+pub use mock::MockHttpFetcher;
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{Condvar, Mutex};
+
+/// A first come, first served lock per key, entries only exist while someone holds or waits on them.
+#[derive(Default)]
+pub struct KeyedLock {
+    queues: Mutex<HashMap<String, Arc<TicketQueue>>>,
+}
+
+/// Callers draw tickets in arrival order and run once their ticket is served.
+#[derive(Default)]
+struct TicketQueue {
+    tickets: Mutex<Tickets>,
+    turn:    Condvar,
+}
+
+#[derive(Default)]
+struct Tickets {
+    next:    u64,
+    serving: u64,
+}
+
+/// Hands the turn to the next ticket when dropped, also when the holder panics.
+struct Turn<'a> {
+    lock:  &'a KeyedLock,
+    key:   &'a str,
+    queue: Arc<TicketQueue>,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        // Tickets are drawn while holding the map, so nobody can queue up between the check and the removal
+        let mut queues = self.lock.queues.lock();
+        let mut tickets = self.queue.tickets.lock();
+        tickets.serving += 1;
+
+        if tickets.serving == tickets.next {
+            queues.remove(self.key);
+        } else {
+            self.queue.turn.notify_all();
+        }
+    }
+}
+
+impl KeyedLock {
+    /// Runs `f` while holding the lock of the given key, callers of the same key run in the order they arrived.
+    pub fn with_lock<T>(&self, key: &str, f: impl FnOnce() -> T) -> T {
+        let (queue, ticket) = {
+            let mut queues = self.queues.lock();
+            let queue = queues.entry(key.to_string()).or_default().clone();
+            let mut tickets = queue.tickets.lock();
+            let ticket = tickets.next;
+            tickets.next += 1;
+            drop(tickets);
+            (queue, ticket)
+        };
+
+        let mut tickets = queue.tickets.lock();
+        while tickets.serving != ticket {
+            queue.turn.wait(&mut tickets);
+        }
+        drop(tickets);
+
+        let _turn = Turn { lock: self, key, queue };
+        f()
+    }
+}
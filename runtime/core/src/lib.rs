@@ -1,14 +1,22 @@
+pub mod abi;
+pub mod compatibility;
 mod context;
 mod core_vm_imports;
+mod data_request_vm_imports;
 mod errors;
+pub mod gas_schedule;
+pub mod http_fetcher;
+mod keyed_lock;
 
 mod memory;
 pub mod metering;
+mod module_cache;
 mod resources_dir;
 pub use resources_dir::resources_home_dir;
 mod runtime;
 mod runtime_context;
 mod safe_wasi_imports;
+mod scheduler;
 mod tally_vm_imports;
 pub mod vm;
 mod vm_imports;
@@ -56,10 +64,13 @@ mod test_override {
 }
 
 pub use context::VmContext;
-pub use core_vm_imports::create_custom_core_imports;
-pub use errors::RuntimeError;
+pub use core_vm_imports::{create_custom_core_imports, SAFE_CORE_IMPORTS};
+pub use errors::{RuntimeError, VmHostError};
+pub use http_fetcher::HttpFetcher;
+#[cfg(feature = "test-utils")]
+pub use module_cache::clear_module_cache;
 pub use runtime::start_runtime;
-pub use runtime_context::RuntimeContext;
+pub use runtime_context::{compiling_engine_config, RuntimeContext};
 pub use safe_wasi_imports::*;
 #[cfg(feature = "test-utils")]
 pub use test_override::*;
@@ -1,8 +1,14 @@
-use wasmer::{wasmparser::Operator, FunctionEnvMut, WASM_PAGE_SIZE};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use wasmer::{wasmparser::Operator, FunctionEnvMut};
 use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
 
 use crate::{
     errors::{Result, VmHostError},
+    gas_schedule::GasSchedule,
+    vm::VmCallData,
+    wasm_cache::WasmModuleSize,
     RuntimeError,
     VmContext,
 };
@@ -36,52 +42,109 @@ pub fn is_accounting(operator: &Operator) -> bool {
     )
 }
 
-const GAS_MULTIPLIER: u64 = 150;
-const GAS_PER_OPERATION: u64 = 125 * GAS_MULTIPLIER;
-const GAS_ACCOUNTING_MULTIPLIER: u64 = 3_000;
-const GAS_MEMORY_GROW_BASE: u64 = 1_000_000;
-
-// Gas for reading and writing bytes
-pub const GAS_PER_BYTE: u64 = 10_000;
-const GAS_PER_BYTE_EXECUTION_RESULT: u64 = 10_000_000;
-
 pub const TERA_GAS: u64 = 1_000_000_000_000;
-// Makes it so you can do roughly 30 http requests with the current gas calculations.
-const GAS_HTTP_FETCH_BASE: u64 = TERA_GAS * 5;
-
-const GAS_BN254_VERIFY_BASE: u64 = TERA_GAS;
-// Makes it so you can do roughly 25 proxy http requests with the current gas calculations.
-const GAS_PROXY_HTTP_FETCH_BASE: u64 = TERA_GAS * 7;
-const GAS_SECP256K1_BASE: u64 = TERA_GAS;
-const GAS_KECCAK256_BASE: u64 = TERA_GAS;
-pub const GAS_STARTUP: u64 = TERA_GAS * 5;
-
-// WASI Gas
-const GAS_ARGS_GET_BASE: u64 = TERA_GAS;
-const GAS_ARGS_SIZES_GET_BASE: u64 = TERA_GAS;
-const GAS_ENVIRON_GET_BASE: u64 = TERA_GAS;
-const GAS_ENVIRON_SIZES_GET_BASE: u64 = TERA_GAS;
-const GAS_FD_WRITE_BASE: u64 = TERA_GAS;
 
 /// Gas cost for each operator
-pub fn get_wasm_operation_gas_cost(operator: &Operator) -> u64 {
+pub fn get_wasm_operation_gas_cost(gas_schedule: &GasSchedule, operator: &Operator) -> u64 {
     if is_accounting(operator) {
-        return GAS_PER_OPERATION * GAS_ACCOUNTING_MULTIPLIER;
+        return gas_schedule
+            .per_operation
+            .saturating_mul(gas_schedule.accounting_multiplier);
     }
 
     match operator {
-        Operator::MemoryGrow { mem } => GAS_MEMORY_GROW_BASE + ((WASM_PAGE_SIZE as u64 * *mem as u64) * GAS_PER_BYTE),
-        _ => GAS_PER_OPERATION,
+        Operator::MemoryGrow { .. } => gas_schedule.memory_grow_base,
+        _ => gas_schedule.per_operation,
+    }
+}
+
+/// Gas cost for each unit of an operator's runtime count operand, like the page count of `memory.grow` or the length
+/// of `memory.fill`
+pub fn get_wasm_operation_unit_gas_cost(gas_schedule: &GasSchedule, operator: &Operator) -> u64 {
+    match operator {
+        Operator::MemoryGrow { .. } => gas_schedule.memory_grow_per_page,
+        Operator::MemoryFill { .. } | Operator::MemoryCopy { .. } | Operator::MemoryInit { .. } => {
+            gas_schedule.memory_bulk_per_byte
+        }
+        Operator::TableGrow { .. }
+        | Operator::TableFill { .. }
+        | Operator::TableCopy { .. }
+        | Operator::TableInit { .. } => gas_schedule.table_bulk_per_element,
+        _ => 0,
     }
 }
 
-pub fn vm_gas_startup_cost(args: &[String]) -> Result<Option<u64>, std::num::TryFromIntError> {
-    let args_bytes_total = args.iter().fold(0, |acc, v| acc + v.len());
-    (GAS_PER_BYTE as usize)
-        .checked_mul(args_bytes_total)
-        .and_then(|cost| cost.checked_add(GAS_STARTUP as usize))
-        .map(|c| c.try_into())
-        .transpose()
+/// What the startup gas is charged for, in the order the parts are added up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
+pub enum StartupGasPart {
+    Base,
+    Args,
+    Envs,
+    WasmBinary,
+    InitialMemory,
+    DataSegments,
+}
+
+impl fmt::Display for StartupGasPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupGasPart::Base => write!(f, "Base cost"),
+            StartupGasPart::Args => write!(f, "Args"),
+            StartupGasPart::Envs => write!(f, "Envs"),
+            StartupGasPart::WasmBinary => write!(f, "WASM binary"),
+            StartupGasPart::InitialMemory => write!(f, "Initial memory"),
+            StartupGasPart::DataSegments => write!(f, "Data segments"),
+        }
+    }
+}
+
+/// Adds up the startup gas, returns the part that brings the total over the gas limit if it does not fit
+pub fn vm_gas_startup_cost(
+    gas_schedule: &GasSchedule,
+    call_data: &VmCallData,
+    module_size: &WasmModuleSize,
+    gas_limit: u64,
+) -> Result<u64, StartupGasPart> {
+    let args_bytes_total = call_data
+        .args
+        .iter()
+        .fold(0u64, |acc, v| acc.saturating_add(v.len() as u64));
+    let parts = [
+        (StartupGasPart::Base, Some(gas_schedule.startup)),
+        (
+            StartupGasPart::Args,
+            gas_schedule.per_byte.checked_mul(args_bytes_total),
+        ),
+        (
+            StartupGasPart::Envs,
+            gas_schedule
+                .startup_per_env_byte
+                .checked_mul(call_data.env_bytes_len() as u64),
+        ),
+        (
+            StartupGasPart::WasmBinary,
+            gas_schedule.startup_per_wasm_byte.checked_mul(module_size.wasm_bytes),
+        ),
+        (
+            StartupGasPart::InitialMemory,
+            gas_schedule
+                .startup_per_memory_page
+                .checked_mul(module_size.initial_memory_pages),
+        ),
+        (
+            StartupGasPart::DataSegments,
+            gas_schedule
+                .startup_per_data_segment_byte
+                .checked_mul(module_size.data_segment_bytes),
+        ),
+    ];
+
+    parts.into_iter().try_fold(0u64, |total, (part, cost)| {
+        cost.and_then(|cost| total.checked_add(cost))
+            .filter(|total| *total <= gas_limit)
+            .ok_or(part)
+    })
 }
 
 #[derive(Debug)]
@@ -99,7 +162,21 @@ pub enum ExternalCallType {
     /// Takes as argument the length of the message
     Secp256k1Verify(u64),
     /// Takes as argument the length of the message
+    Secp256k1Recover(u64),
+    /// Takes as argument the number of signatures and the length of the packed entries
+    Secp256k1VerifyBatch(u64, u64),
+    /// Takes as argument the length of the message
     Keccak256(u64),
+    /// Takes as argument the length of the message
+    Sha256(u64),
+    /// Takes as argument the length of the message
+    Blake2b256(u64),
+    /// Takes as argument the length of the message
+    Ripemd160(u64),
+    /// Takes as argument the number of hashed input bytes and the number of proof nodes
+    MerkleVerify(u64, u64),
+    /// Takes as argument the length of the message, signature and public key
+    Ed25519Verify(u64),
 
     /// WASI Imports
     ArgsGet(u64),
@@ -110,10 +187,81 @@ pub enum ExternalCallType {
     FdWrite(u64),
 }
 
+/// A base price plus a price per unit, saturating so oversized guest inputs run out of gas instead of overflowing
+fn base_plus_units(base: u64, per_unit: u64, units: u64) -> u64 {
+    base.saturating_add(per_unit.saturating_mul(units))
+}
+
+fn external_call_gas_cost(gas_schedule: &GasSchedule, external_call_type: ExternalCallType) -> u64 {
+    let per_byte = gas_schedule.per_byte;
+
+    match external_call_type {
+        ExternalCallType::ExecutionResult(bytes_length) => {
+            gas_schedule.per_byte_execution_result.saturating_mul(bytes_length)
+        }
+        ExternalCallType::HttpFetchRequest(bytes_length) => {
+            base_plus_units(gas_schedule.http_fetch_base, per_byte, bytes_length)
+        }
+        ExternalCallType::HttpFetchResponse(bytes_length) => per_byte.saturating_mul(bytes_length),
+        ExternalCallType::Bn254Verify(bytes_length) => {
+            base_plus_units(gas_schedule.bn254_verify_base, per_byte, bytes_length)
+        }
+        ExternalCallType::ProxyHttpFetchRequest(bytes_length) => {
+            base_plus_units(gas_schedule.proxy_http_fetch_base, per_byte, bytes_length)
+        }
+        ExternalCallType::Secp256k1Verify(bytes_length) => base_plus_units(
+            gas_schedule.secp256k1_base.saturating_add(gas_schedule.keccak256_base),
+            per_byte,
+            bytes_length,
+        ),
+        ExternalCallType::Secp256k1Recover(bytes_length) => base_plus_units(
+            gas_schedule
+                .secp256k1_recover_base
+                .saturating_add(gas_schedule.keccak256_base),
+            per_byte,
+            bytes_length,
+        ),
+        ExternalCallType::Secp256k1VerifyBatch(signatures, bytes_length) => base_plus_units(
+            base_plus_units(gas_schedule.secp256k1_verify_batch_base, per_byte, bytes_length),
+            gas_schedule.secp256k1_verify_batch_per_signature,
+            signatures,
+        ),
+        ExternalCallType::Keccak256(bytes_length) => {
+            base_plus_units(gas_schedule.keccak256_base, per_byte, bytes_length)
+        }
+        ExternalCallType::Sha256(bytes_length) => base_plus_units(gas_schedule.sha256_base, per_byte, bytes_length),
+        ExternalCallType::Blake2b256(bytes_length) => {
+            base_plus_units(gas_schedule.blake2b_256_base, per_byte, bytes_length)
+        }
+        ExternalCallType::Ripemd160(bytes_length) => {
+            base_plus_units(gas_schedule.ripemd160_base, per_byte, bytes_length)
+        }
+        ExternalCallType::MerkleVerify(leaf_length, proof_nodes) => base_plus_units(
+            base_plus_units(gas_schedule.merkle_verify_base, per_byte, leaf_length),
+            gas_schedule.merkle_verify_per_node,
+            proof_nodes,
+        ),
+        ExternalCallType::Ed25519Verify(bytes_length) => {
+            base_plus_units(gas_schedule.ed25519_verify_base, per_byte, bytes_length)
+        }
+        ExternalCallType::ArgsGet(bytes_length) => base_plus_units(gas_schedule.args_get_base, per_byte, bytes_length),
+        ExternalCallType::ArgsSizesGet(bytes_length) => {
+            base_plus_units(gas_schedule.args_sizes_get_base, per_byte, bytes_length)
+        }
+        ExternalCallType::EnvironGet(bytes_length) => {
+            base_plus_units(gas_schedule.environ_get_base, per_byte, bytes_length)
+        }
+        ExternalCallType::EnvironSizesGet(bytes_length) => {
+            base_plus_units(gas_schedule.environ_sizes_get_base, per_byte, bytes_length)
+        }
+        ExternalCallType::FdWrite(iovs_len) => base_plus_units(gas_schedule.fd_write_base, per_byte, iovs_len),
+    }
+}
+
 pub fn check_enough_gas(gas_cost: u64, remaining_gas: u64, gas_limit: u64) -> Result<u64> {
     let gas_used = gas_limit - remaining_gas;
 
-    if (gas_cost + gas_used) > gas_limit {
+    if gas_cost.saturating_add(gas_used) > gas_limit {
         return Err(RuntimeError::OutOfGas);
     }
 
@@ -128,32 +276,12 @@ pub fn apply_gas_cost(external_call_type: ExternalCallType, env: &mut FunctionEn
     }?;
 
     if let Some(gas_limit) = context.call_data.gas_limit {
+        let gas_cost = external_call_gas_cost(&context.call_data.gas_schedule, external_call_type);
         let remaining_gas = match get_remaining_points(env, &instance) {
             MeteringPoints::Exhausted => 0,
             MeteringPoints::Remaining(remaining_gas) => remaining_gas,
         };
 
-        let gas_cost = match external_call_type {
-            ExternalCallType::ExecutionResult(bytes_length) => GAS_PER_BYTE_EXECUTION_RESULT * bytes_length,
-            ExternalCallType::HttpFetchRequest(bytes_length) => GAS_HTTP_FETCH_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::HttpFetchResponse(bytes_length) => GAS_PER_BYTE * bytes_length,
-            ExternalCallType::Bn254Verify(bytes_length) => GAS_BN254_VERIFY_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::ProxyHttpFetchRequest(bytes_length) => {
-                GAS_PROXY_HTTP_FETCH_BASE + (GAS_PER_BYTE * bytes_length)
-            }
-            ExternalCallType::Secp256k1Verify(bytes_length) => {
-                GAS_SECP256K1_BASE + GAS_KECCAK256_BASE + (GAS_PER_BYTE * bytes_length)
-            }
-            ExternalCallType::Keccak256(bytes_length) => GAS_KECCAK256_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::ArgsGet(bytes_length) => GAS_ARGS_GET_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::ArgsSizesGet(bytes_length) => GAS_ARGS_SIZES_GET_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::EnvironGet(bytes_length) => GAS_ENVIRON_GET_BASE + (GAS_PER_BYTE * bytes_length),
-            ExternalCallType::EnvironSizesGet(bytes_length) => {
-                GAS_ENVIRON_SIZES_GET_BASE + (GAS_PER_BYTE * bytes_length)
-            }
-            ExternalCallType::FdWrite(iovs_len) => GAS_FD_WRITE_BASE + (GAS_PER_BYTE * iovs_len),
-        };
-
         let gas_left = check_enough_gas(gas_cost, remaining_gas, gas_limit)?;
         set_remaining_points(env, &instance, gas_left);
     }
@@ -0,0 +1,97 @@
+#[cfg(feature = "test-utils")]
+use std::path::Path;
+use std::{collections::HashMap, path::PathBuf};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use wasmer::{Engine, Module};
+
+use crate::{runtime_context::make_runtime_engine, wasm_cache::WasmModuleSize};
+
+/// Maximum amount of compiled modules kept in memory
+const MODULE_CACHE_CAPACITY: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleCacheKey {
+    /// Location of the artifact in the WASM cache, which covers the sedad home,
+    /// the library versions and the cache ID
+    pub wasm_path:     PathBuf,
+    /// Covers the memory limit and everything else the artifact was compiled with
+    pub engine_config: String,
+}
+
+struct CachedModule {
+    module:      Module,
+    module_size: WasmModuleSize,
+    last_used:   u64,
+}
+
+#[derive(Default)]
+struct ModuleCache {
+    engines: HashMap<u32, Engine>,
+    modules: HashMap<ModuleCacheKey, CachedModule>,
+    clock:   u64,
+}
+
+lazy_static! {
+    static ref MODULE_CACHE: Mutex<ModuleCache> = Mutex::new(ModuleCache::default());
+}
+
+/// A module can only be instantiated in a store of the engine it was loaded with,
+/// so all executions with the same memory limit share one runtime engine.
+pub fn runtime_engine(max_memory_pages: u32) -> Engine {
+    MODULE_CACHE
+        .lock()
+        .engines
+        .entry(max_memory_pages)
+        .or_insert_with(|| make_runtime_engine(max_memory_pages))
+        .clone()
+}
+
+pub fn get_module(key: &ModuleCacheKey) -> Option<(Module, WasmModuleSize)> {
+    let mut cache = MODULE_CACHE.lock();
+    cache.clock += 1;
+    let clock = cache.clock;
+
+    cache.modules.get_mut(key).map(|cached| {
+        cached.last_used = clock;
+        (cached.module.clone(), cached.module_size)
+    })
+}
+
+pub fn insert_module(key: ModuleCacheKey, module: Module, module_size: WasmModuleSize) {
+    let mut cache = MODULE_CACHE.lock();
+    cache.clock += 1;
+    let clock = cache.clock;
+
+    // Evict the least recently used module to stay within the capacity
+    if !cache.modules.contains_key(&key) && cache.modules.len() >= MODULE_CACHE_CAPACITY {
+        let least_recently_used = cache
+            .modules
+            .iter()
+            .min_by_key(|(_, cached)| cached.last_used)
+            .map(|(key, _)| key.clone());
+
+        if let Some(least_recently_used) = least_recently_used {
+            cache.modules.remove(&least_recently_used);
+        }
+    }
+
+    cache.modules.insert(
+        key,
+        CachedModule {
+            module,
+            module_size,
+            last_used: clock,
+        },
+    );
+}
+
+/// Drops the modules loaded from the WASM cache under the given sedad home
+#[cfg(feature = "test-utils")]
+pub fn clear_module_cache(sedad_home: &Path) {
+    MODULE_CACHE
+        .lock()
+        .modules
+        .retain(|key, _| !key.wasm_path.starts_with(sedad_home));
+}
@@ -7,9 +7,12 @@ use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, M
 use wasmer_wasix::{Pipe, WasiEnv, WasiRuntimeError};
 
 use crate::{
+    abi::detect_abi_version,
+    compatibility::validate_imports,
     context::VmContext,
     metering::vm_gas_startup_cost,
     runtime_context::RuntimeContext,
+    scheduler::run_scheduled,
     vm::*,
     vm_imports::create_wasm_imports,
 };
@@ -57,13 +60,8 @@ fn _internal_run_vm(
 ) -> ExecutionResult<(Vec<u8>, i32, u64)> {
     // If the gas limit is set, we need to calculate the startup cost
     let gas_cost = if let Some(gas_limit) = call_data.gas_limit {
-        let Ok(Some(gas_cost)): Result<Option<u64>, _> = vm_gas_startup_cost(&call_data.args) else {
-            return Err(VmResultStatus::GasStartupCostTooHigh(gas_limit));
-        };
-        if gas_cost > gas_limit {
-            return Err(VmResultStatus::GasStartupCostTooHigh(gas_limit));
-        }
-        gas_cost
+        vm_gas_startup_cost(&call_data.gas_schedule, &call_data, &context.module_size, gas_limit)
+            .map_err(|part| VmResultStatus::GasStartupCostTooHigh(part, gas_limit))?
     } else {
         0
     };
@@ -82,7 +80,12 @@ fn _internal_run_vm(
         .finalize(&mut context.wasm_store)
         .map_err(|_| VmResultStatus::WasiEnvInitializeFailure)?;
 
-    let vm_context = VmContext::create_vm_context(&mut context.wasm_store, wasi_env.env.clone(), call_data.clone());
+    let vm_context = VmContext::create_vm_context(
+        &mut context.wasm_store,
+        wasi_env.env.clone(),
+        call_data.clone(),
+        context.http_fetcher.clone(),
+    );
 
     let imports = create_wasm_imports(
         &mut context.wasm_store,
@@ -93,10 +96,13 @@ fn _internal_run_vm(
     )
     .map_err(|_| VmResultStatus::FailedToCreateVMImports)?;
 
-    // let _lock = get_instance_mutex().lock();
+    let invalid_imports = validate_imports(&context.wasm_store, &context.wasm_module, &imports);
+    if !invalid_imports.is_empty() {
+        return Err(VmResultStatus::InvalidImports(invalid_imports, gas_cost));
+    }
+
     let wasmer_instance = Instance::new(&mut context.wasm_store, &context.wasm_module, &imports)
         .map_err(|e| VmResultStatus::FailedToCreateWasmerInstance(e.to_string(), gas_cost))?;
-    // drop(_lock);
 
     vm_context.as_mut(&mut context.wasm_store).instance = Some(wasmer_instance.clone());
 
@@ -113,10 +119,12 @@ fn _internal_run_vm(
         .initialize(&mut context.wasm_store, wasmer_instance.clone())
         .map_err(|_| VmResultStatus::FailedToGetWASMFn(gas_cost))?;
 
-    // spawn the actual VM run on a separate thread
+    // spawn the actual VM run on a separate thread, keeping the span with the call ID
+    let span = tracing::Span::current();
     #[allow(clippy::type_complexity)]
     let ((exec_bytes, exit_code, gas_used), local_stderr) =
         std::thread::spawn(move || -> Result<((Vec<u8>, i32, u64), Vec<String>), VmResultStatus> {
+            let _enter = span.enter();
             tracing::debug!("Calling WASM entrypoint");
             let main_func = wasmer_instance
                 .exports
@@ -223,16 +231,24 @@ pub fn start_runtime(
     stdout_limit: usize,
     stderr_limit: usize,
 ) -> VmResult {
-    tracing::debug!("Starting runtime");
     let mut stdout: Vec<String> = vec![];
     let mut stderr: Vec<String> = vec![];
 
-    let vm_execution_result = internal_run_vm(call_data, context, &mut stdout, &mut stderr, stdout_limit, stderr_limit);
+    let abi_version = detect_abi_version(&context.wasm_module);
+    let requested_call_id = call_data.call_id.clone();
+    let (call_key, vm_execution_result) = run_scheduled(requested_call_id.as_deref(), |call_key| {
+        let span = tracing::info_span!("vm", call_key = %call_key);
+        let _enter = span.enter();
+
+        tracing::debug!("Starting runtime");
+        let res = internal_run_vm(call_data, context, &mut stdout, &mut stderr, stdout_limit, stderr_limit);
+        tracing::info!("TALLY VM execution completed");
+        res
+    });
 
-    tracing::info!("TALLY VM execution completed");
     match vm_execution_result {
         Ok((result, exit_code, gas_used)) => {
-            tracing::info!("TALLY VM gas used: {gas_used}");
+            tracing::info!(call_key = %call_key, "TALLY VM gas used: {gas_used}");
             VmResult {
                 stdout,
                 stderr,
@@ -245,6 +261,8 @@ pub fn start_runtime(
                     },
                 },
                 result: Some(result),
+                call_id: call_key.into_call_id(),
+                abi_version,
             }
         }
         Err(error) => {
@@ -255,6 +273,8 @@ pub fn start_runtime(
                 result: None,
                 gas_used: info.1,
                 exit_info: info.0,
+                call_id: call_key.into_call_id(),
+                abi_version,
             }
         }
     }
@@ -1,7 +1,8 @@
 use std::{path::Path, sync::Arc};
 
 use wasmer::{
-    sys::{BaseTunables, CompilerConfig, NativeEngineExt, Singlepass},
+    sys::{BaseTunables, CompilerConfig, Cranelift, NativeEngineExt, Singlepass},
+    wasmparser::Operator,
     Engine,
     Module,
     Pages,
@@ -11,11 +12,24 @@ use wasmer::{
 use wasmer_middlewares::Metering;
 
 use crate::{
-    errors::Result,
+    errors::{Result, VmHostError},
+    gas_schedule::GasSchedule,
+    http_fetcher::HttpFetcher,
     memory::LimitingTunables,
-    metering::get_wasm_operation_gas_cost,
-    vm::{VmCallData, WasmId},
-    wasm_cache::{get_full_wasm_path_from_id, valid_wasm_cache_id, wasm_cache_id, wasm_cache_load, wasm_cache_store},
+    metering::{get_wasm_operation_gas_cost, get_wasm_operation_unit_gas_cost},
+    module_cache::{get_module, insert_module, runtime_engine, ModuleCacheKey},
+    vm::{VmCallData, WasmEngine, WasmId},
+    wasm_cache::{
+        get_full_wasm_path_from_id,
+        is_well_formed_wasm_cache_id,
+        touch_cache_entry,
+        valid_wasm_cache_id,
+        wasm_cache_id,
+        wasm_cache_load,
+        wasm_cache_store,
+        with_wasm_cache_lock,
+        WasmModuleSize,
+    },
 };
 
 pub fn make_runtime_engine(max_memory_pages: u32) -> Engine {
@@ -27,12 +41,49 @@ pub fn make_runtime_engine(max_memory_pages: u32) -> Engine {
     engine
 }
 
-pub fn make_compiling_engine(max_memory_pages: u32) -> Store {
-    let mut compiler = Singlepass::new();
+/// Everything that influences the compiled artifact besides the WASM binary and the
+/// library versions (those are already part of the cache directory).
+/// Operator costs are compiled into the artifact, so the gas schedule is part of it as well.
+/// Its hash names the cache directory, so configurations do not overwrite each other's artifacts.
+pub fn compiling_engine_config(
+    wasm_engine: &WasmEngine,
+    max_memory_pages: u32,
+    gas_schedule_version: &str,
+    gas_schedule: &GasSchedule,
+) -> String {
+    format!(
+        "{wasm_engine};{};max_memory_pages={max_memory_pages};gas_schedule={gas_schedule_version}:{}",
+        Target::default().triple(),
+        gas_schedule.hash()
+    )
+}
 
-    let metering = Arc::new(Metering::new(0, get_wasm_operation_gas_cost));
-    compiler.push_middleware(metering);
-    let mut engine = Engine::from(compiler);
+pub fn make_compiling_engine(wasm_engine: &WasmEngine, max_memory_pages: u32, gas_schedule: &GasSchedule) -> Store {
+    let operation_gas_schedule = gas_schedule.clone();
+    let unit_gas_schedule = gas_schedule.clone();
+    // Both compilers get the metering middleware, gas usage must not depend on the engine
+    let metering = Arc::new(
+        Metering::new(0, move |operator: &Operator| {
+            get_wasm_operation_gas_cost(&operation_gas_schedule, operator)
+        })
+        .with_unit_cost_function(move |operator: &Operator| {
+            get_wasm_operation_unit_gas_cost(&unit_gas_schedule, operator)
+        }),
+    );
+    let mut engine = match wasm_engine {
+        WasmEngine::Singlepass => {
+            let mut compiler = Singlepass::new();
+            compiler.push_middleware(metering);
+            Engine::from(compiler)
+        }
+        WasmEngine::Cranelift => {
+            let mut compiler = Cranelift::new();
+            // NaN bit patterns differ between platforms otherwise
+            compiler.canonicalize_nans(true);
+            compiler.push_middleware(metering);
+            Engine::from(compiler)
+        }
+    };
 
     let base = BaseTunables::for_target(&Target::default());
     let tunables = LimitingTunables::new(base, Pages(max_memory_pages));
@@ -42,52 +93,94 @@ pub fn make_compiling_engine(max_memory_pages: u32) -> Store {
 }
 
 pub struct RuntimeContext {
-    pub wasm_store:  Store,
-    pub wasm_module: Module,
-    pub wasm_hash:   String,
+    pub wasm_store:   Store,
+    pub wasm_module:  Module,
+    /// Sizes of the binary the module was compiled from, charged as part of the startup gas
+    pub module_size:  WasmModuleSize,
+    pub wasm_hash:    String,
+    pub http_fetcher: Option<Arc<dyn HttpFetcher>>,
 }
 
 impl RuntimeContext {
     pub fn new(sedad_home: &Path, call_data: &VmCallData) -> Result<Self> {
-        let engine = make_runtime_engine(call_data.max_memory_pages);
-        let store = Store::new(engine);
-
-        let (wasm_module, wasm_hash) = match &call_data.wasm_id {
-            WasmId::Bytes(wasm_bytes) => {
-                let wasm_id = wasm_cache_id(wasm_bytes);
-                let wasm_path = get_full_wasm_path_from_id(sedad_home, &wasm_id);
-
-                let mut compiled = wasm_path.exists() && wasm_path.is_file();
-                if compiled && !valid_wasm_cache_id(&wasm_path) {
-                    compiled = false;
+        let store = Store::new(runtime_engine(call_data.max_memory_pages));
+        let engine_config = compiling_engine_config(
+            &call_data.wasm_engine,
+            call_data.max_memory_pages,
+            &call_data.gas_schedule_version,
+            &call_data.gas_schedule,
+        );
+
+        let wasm_hash = match &call_data.wasm_id {
+            WasmId::Id(wasm_id) => {
+                if !is_well_formed_wasm_cache_id(wasm_id) {
+                    Err(VmHostError::InvalidWasmCacheId(wasm_id.to_string()))?;
                 }
 
-                if compiled {
-                    let wasm_module = wasm_cache_load(&store, &wasm_path)?;
-                    return Ok(Self {
-                        wasm_module,
-                        wasm_store: store,
-                        wasm_hash: wasm_id,
-                    });
-                }
+                wasm_id.to_string()
+            }
+            WasmId::Bytes(wasm_bytes) => wasm_cache_id(wasm_bytes),
+        };
 
-                // If not, compile and cache it
-                let wasm_module = wasm_cache_store(
-                    sedad_home,
-                    &make_compiling_engine(call_data.max_memory_pages),
-                    &store,
-                    &wasm_id,
-                    wasm_bytes,
-                )?;
+        let wasm_path = get_full_wasm_path_from_id(sedad_home, &call_data.wasm_engine, &engine_config, &wasm_hash);
+        let cache_key = ModuleCacheKey {
+            wasm_path:     wasm_path.clone(),
+            engine_config: engine_config.clone(),
+        };
 
-                (wasm_module, wasm_id)
+        let (wasm_module, module_size) = match get_module(&cache_key) {
+            Some(cached) => {
+                // Keeps the artifact from being pruned while it is only served from memory
+                touch_cache_entry(&wasm_path);
+                cached
+            }
+            None => {
+                let cached = with_wasm_cache_lock(&wasm_hash, || -> Result<(Module, WasmModuleSize)> {
+                    let compiled = wasm_path.is_file() && valid_wasm_cache_id(&wasm_path);
+
+                    if compiled {
+                        if let Some(loaded) = wasm_cache_load(&store, &wasm_path, &engine_config)? {
+                            return Ok(loaded);
+                        }
+                    }
+
+                    match &call_data.wasm_id {
+                        // Without the binary we cannot recompile, so a rejected entry is as good as missing
+                        WasmId::Id(_) => Err(VmHostError::WasmNotCached(wasm_hash.clone()).into()),
+                        // If not, compile and cache it
+                        WasmId::Bytes(wasm_bytes) => wasm_cache_store(
+                            sedad_home,
+                            &make_compiling_engine(
+                                &call_data.wasm_engine,
+                                call_data.max_memory_pages,
+                                &call_data.gas_schedule,
+                            ),
+                            &store,
+                            &call_data.wasm_engine,
+                            &wasm_hash,
+                            &engine_config,
+                            wasm_bytes,
+                        ),
+                    }
+                })?;
+
+                insert_module(cache_key, cached.0.clone(), cached.1);
+                cached
             }
         };
 
         Ok(Self {
             wasm_module,
+            module_size,
             wasm_store: store,
             wasm_hash,
+            http_fetcher: None,
         })
     }
+
+    /// Sets the fetcher that serves `http_fetch` and `proxy_http_fetch` in data request mode
+    pub fn with_http_fetcher(mut self, http_fetcher: Arc<dyn HttpFetcher>) -> Self {
+        self.http_fetcher = Some(http_fetcher);
+        self
+    }
 }
@@ -0,0 +1,61 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use lazy_static::lazy_static;
+
+use crate::keyed_lock::KeyedLock;
+
+lazy_static! {
+    static ref CALL_ID_LOCKS: KeyedLock = KeyedLock::default();
+}
+
+static AUTO_CALL_KEY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies an execution in the scheduler.
+/// Generated keys have their own variant, so they can never collide with a call ID given by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CallKey {
+    User(String),
+    Auto(u64),
+}
+
+impl CallKey {
+    /// The call ID given by the caller, generated keys are not reported back
+    pub fn into_call_id(self) -> Option<String> {
+        match self {
+            CallKey::User(call_id) => Some(call_id),
+            CallKey::Auto(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for CallKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallKey::User(call_id) => write!(f, "user:{call_id}"),
+            CallKey::Auto(number) => write!(f, "auto:{number}"),
+        }
+    }
+}
+
+/// Runs `f` once no other execution with the same call ID is running, executions sharing an ID
+/// run in the order they were scheduled and executions with different IDs run concurrently.
+/// Calls without an ID get a generated key and run immediately.
+///
+/// Returns the key the execution ran under together with its result.
+pub fn run_scheduled<T>(call_id: Option<&str>, f: impl FnOnce(&CallKey) -> T) -> (CallKey, T) {
+    match call_id {
+        Some(call_id) => {
+            let call_key = CallKey::User(call_id.to_string());
+            let result = CALL_ID_LOCKS.with_lock(call_id, || f(&call_key));
+            (call_key, result)
+        }
+        None => {
+            let call_key = CallKey::Auto(AUTO_CALL_KEY_COUNTER.fetch_add(1, Ordering::Relaxed));
+            let result = f(&call_key);
+            (call_key, result)
+        }
+    }
+}
@@ -1,47 +1,22 @@
-use wasmer::{Exports, FunctionEnv, Imports, Store};
+use lazy_static::lazy_static;
+use wasmer::{FunctionEnv, Imports, Store};
 
-use crate::{create_custom_core_imports, VmContext, SAFE_WASI_IMPORTS};
+use crate::{
+    vm_imports::{create_imports_with_fetch, safe_imports_with_fetch},
+    VmContext,
+};
 
 mod http_fetch;
 mod proxy_http_fetch;
 
-use lazy_static::lazy_static;
-
 lazy_static! {
-    pub static ref SAFE_TALLY_IMPORTS: Vec<String> = {
-        [
-            "execution_result",
-            "http_fetch",
-            "proxy_http_fetch",
-            "call_result_write",
-            "secp256k1_verify",
-            "keccak256",
-        ]
-        .iter()
-        .map(|import| import.to_string())
-        .chain(SAFE_WASI_IMPORTS.to_vec())
-        .collect()
-    };
+    /// Tally programs get every core import and may also fetch over HTTP
+    pub static ref SAFE_TALLY_IMPORTS: Vec<String> = safe_imports_with_fetch();
 }
 
 pub fn create_custom_tally_imports(store: &mut Store, vm_context: &FunctionEnv<VmContext>) -> Imports {
-    let core_imports = create_custom_core_imports(store, vm_context);
-    let mut tally_exports = Exports::new();
-    let mut final_imports = Imports::new();
-
-    tally_exports.insert("http_fetch", http_fetch::http_fetch_import_obj(store, vm_context));
-    tally_exports.insert(
-        "proxy_http_fetch",
-        proxy_http_fetch::proxy_http_fetch_import_obj(store, vm_context),
-    );
-
-    if let Some(core_exports) = core_imports.get_namespace_exports("seda_v1") {
-        for (export_name, export) in core_exports.iter() {
-            tally_exports.insert(export_name, export.clone());
-        }
-    }
-
-    final_imports.register_namespace("seda_v1", tally_exports);
+    let http_fetch = http_fetch::http_fetch_import_obj(store, vm_context);
+    let proxy_http_fetch = proxy_http_fetch::proxy_http_fetch_import_obj(store, vm_context);
 
-    final_imports
+    create_imports_with_fetch(store, vm_context, http_fetch, proxy_http_fetch)
 }
@@ -4,6 +4,12 @@ use std::collections::BTreeMap;
 use seda_sdk_rs::bytes::{Bytes, ToBytes};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    abi::AbiVersion,
+    gas_schedule::{GasSchedule, DEFAULT_GAS_SCHEDULE_VERSION},
+    metering::StartupGasPart,
+};
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary))]
 pub enum ConsensusType {
@@ -23,9 +29,8 @@ impl fmt::Display for ConsensusType {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary))]
 pub enum WasmId {
-    // Doesn't exist for Singlepass compiler
-    // /// The ID of the WASM file, loads from cache
-    // Id(String),
+    /// The cache ID of an already compiled WASM file, loads from cache
+    Id(String),
     // Unlikely to be used for tallyvm?
     // /// The path on disk of the WASM file
     // Path(String),
@@ -33,6 +38,13 @@ pub enum WasmId {
     Bytes(Vec<u8>),
 }
 
+#[cfg(feature = "test-utils")]
+impl From<Vec<u8>> for WasmId {
+    fn from(wasm_bytes: Vec<u8>) -> Self {
+        Self::Bytes(wasm_bytes)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary))]
 pub enum VmType {
@@ -41,10 +53,23 @@ pub enum VmType {
     Core,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
 pub enum WasmEngine {
-    Cranelift,
+    /// Slower code but faster and predictable compilation, used for everything on-chain
+    #[default]
     Singlepass,
+    /// Optimized code for workloads where runtime matters more than compile latency
+    Cranelift,
+}
+
+impl fmt::Display for WasmEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmEngine::Singlepass => write!(f, "singlepass"),
+            WasmEngine::Cranelift => write!(f, "cranelift"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -52,7 +77,8 @@ pub enum WasmEngine {
 pub struct VmCallData {
     /// Identifier for differentiating between processes
     /// If assigned, the runtime will queue the call if there is already a process running with that id
-    /// If left empty it auto assigns an id and will run immidiatly
+    /// If left empty it runs immidiatly under a generated key that is only recorded on the tracing spans
+    /// A given id is recorded on the tracing spans and the [`VmResult`]
     pub call_id: Option<String>,
 
     /// Identifier for which WASM file to pick
@@ -76,7 +102,16 @@ pub struct VmCallData {
     /// Which VM context you want to run in
     pub vm_type: VmType,
 
+    /// Which compiler the WASM binary is compiled with
+    pub wasm_engine: WasmEngine,
+
     pub max_memory_pages: u32,
+
+    /// Prices used to meter the execution
+    pub gas_schedule: GasSchedule,
+
+    /// Name of the version `gas_schedule` was registered under
+    pub gas_schedule_version: String,
 }
 
 impl VmCallData {
@@ -89,23 +124,26 @@ impl VmCallData {
         // Sum up the length of each environment variable key and value plus 2 bytes for null terminators
         self.envs
             .iter()
-            .fold(0, |acc, (key, val)| acc + key.len() + val.len() + 2)
+            .fold(0usize, |acc, (key, val)| acc.saturating_add(key.len() + val.len() + 2))
     }
 }
 
 impl Default for VmCallData {
     fn default() -> Self {
         Self {
-            vm_type:          VmType::Tally,
-            args:             vec![],
-            call_id:          None,
-            envs:             Default::default(),
-            program_name:     "default".to_string(),
-            start_func:       None,
-            wasm_id:          WasmId::Bytes(vec![]),
-            gas_limit:        None,
+            vm_type:              VmType::Tally,
+            wasm_engine:          WasmEngine::Singlepass,
+            args:                 vec![],
+            call_id:              None,
+            envs:                 Default::default(),
+            program_name:         "default".to_string(),
+            start_func:           None,
+            wasm_id:              WasmId::Bytes(vec![]),
+            gas_limit:            None,
             // 160 pages * 64KB per page = 10MB maximum memory
-            max_memory_pages: 160,
+            max_memory_pages:     160,
+            gas_schedule:         GasSchedule::default(),
+            gas_schedule_version: DEFAULT_GAS_SCHEDULE_VERSION.to_string(),
         }
     }
 }
@@ -114,8 +152,8 @@ impl fmt::Display for VmCallData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "VmCallData {{ call_id: {:?}, args: {:?}, envs: {:?}, program_name: {:?}, start_func: {:?}, vm_type: {:?} }}",
-            self.call_id, self.args, self.envs, self.program_name, self.start_func, self.vm_type
+            "VmCallData {{ call_id: {:?}, args: {:?}, envs: {:?}, program_name: {:?}, start_func: {:?}, vm_type: {:?}, wasm_engine: {} }}",
+            self.call_id, self.args, self.envs, self.program_name, self.start_func, self.vm_type, self.wasm_engine
         )
     }
 }
@@ -146,24 +184,30 @@ impl From<(String, i32)> for ExitInfo {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary, PartialEq))]
 pub struct VmResult {
-    pub stdout:    Vec<String>,
-    pub stderr:    Vec<String>,
-    pub result:    Option<Vec<u8>>,
-    pub exit_info: ExitInfo,
-    pub gas_used:  u64,
+    pub stdout:      Vec<String>,
+    pub stderr:      Vec<String>,
+    pub result:      Option<Vec<u8>>,
+    pub exit_info:   ExitInfo,
+    pub gas_used:    u64,
+    /// The call ID the execution ran under, `None` if the caller did not give one, see [`VmCallData::call_id`]
+    pub call_id:     Option<String>,
+    /// The host ABI version the program was built against, `None` if it uses no SEDA host imports
+    pub abi_version: Option<AbiVersion>,
 }
 
 impl VmResult {
     pub fn create_err<M: ToString>(message: M, exit_code: i32) -> VmResult {
         VmResult {
-            stdout:    vec![],
-            stderr:    vec![message.to_string()],
-            result:    None,
-            exit_info: ExitInfo {
+            stdout:      vec![],
+            stderr:      vec![message.to_string()],
+            result:      None,
+            exit_info:   ExitInfo {
                 exit_message: message.to_string(),
                 exit_code,
             },
-            gas_used:  0,
+            gas_used:    0,
+            call_id:     None,
+            abi_version: None,
         }
     }
 }
@@ -202,7 +246,10 @@ pub enum VmResultStatus {
     FailedToGetWASMMemory(u64),
     /// When the execution result size exceeds the maximum allowed size
     ResultSizeExceeded(u64),
-    GasStartupCostTooHigh(u64),
+    /// When the startup gas does not fit in the gas limit, with the part that did not fit anymore
+    GasStartupCostTooHigh(StartupGasPart, u64),
+    /// When the WASM binary imports something the VM does not provide
+    InvalidImports(Vec<String>, u64),
 }
 
 impl From<VmResultStatus> for ExitInfoWithGasUsed {
@@ -244,14 +291,18 @@ impl From<VmResultStatus> for ExitInfoWithGasUsed {
                 ("Error: Execution result size exceeds maximum allowed size".into(), 13).into(),
                 gas_used,
             ),
-            VmResultStatus::GasStartupCostTooHigh(gas_limit) => Self(
+            VmResultStatus::GasStartupCostTooHigh(part, gas_limit) => Self(
                 (
-                    "Error: Gas startup cost is too expensive. Args might be too large.".into(),
+                    format!("Error: Gas startup cost is too expensive. {part} might be too large."),
                     14,
                 )
                     .into(),
                 gas_limit,
             ),
+            VmResultStatus::InvalidImports(imports, startup_cost) => Self(
+                (format!("Error: Invalid imports: {}", imports.join(", ")), 15).into(),
+                startup_cost,
+            ),
         }
     }
 }
@@ -1,8 +1,12 @@
-use wasmer::{Exports, FunctionEnv, Imports, Module, Store, WasmPtr};
+use wasmer::{Exports, Function, FunctionEnv, Imports, Module, Store, WasmPtr};
 use wasmer_wasix::{get_wasi_version, WasiFunctionEnv};
 
 use crate::{
-    errors::Result,
+    abi::{imported_abi_versions, AbiVersion},
+    core_vm_imports::SAFE_CORE_IMPORTS,
+    create_custom_core_imports,
+    data_request_vm_imports::{create_custom_data_request_imports, SAFE_DATA_REQUEST_IMPORTS},
+    errors::{Result, VmHostError},
     tally_vm_imports::{create_custom_tally_imports, SAFE_TALLY_IMPORTS},
     vm::{VmCallData, VmType},
     wasi_vm_imports::{
@@ -13,6 +17,45 @@ use crate::{
     VmContext,
 };
 
+/// WASI imports we always provide ourselves, regardless of the VM type
+pub const POLYFILLED_WASI_IMPORTS: [&str; 2] = ["random_get", "clock_time_get"];
+
+/// Imports the tally and data request modes add to the core imports, each mode has its own implementation
+pub const FETCH_IMPORTS: [&str; 2] = ["http_fetch", "proxy_http_fetch"];
+
+/// The core imports plus [`FETCH_IMPORTS`]
+pub(crate) fn safe_imports_with_fetch() -> Vec<String> {
+    SAFE_CORE_IMPORTS
+        .iter()
+        .cloned()
+        .chain(FETCH_IMPORTS.iter().map(|import| import.to_string()))
+        .collect()
+}
+
+/// The core imports plus the `http_fetch` and `proxy_http_fetch` of a mode
+pub(crate) fn create_imports_with_fetch(
+    store: &mut Store,
+    vm_context: &FunctionEnv<VmContext>,
+    http_fetch: Function,
+    proxy_http_fetch: Function,
+) -> Imports {
+    let mut imports = create_custom_core_imports(store, vm_context);
+    let [http_fetch_name, proxy_http_fetch_name] = FETCH_IMPORTS;
+    imports.define(AbiVersion::V1.namespace(), http_fetch_name, http_fetch);
+    imports.define(AbiVersion::V1.namespace(), proxy_http_fetch_name, proxy_http_fetch);
+
+    imports
+}
+
+/// The imports a program is allowed to use when running in the given VM type
+pub fn safe_imports(vm_type: &VmType) -> &'static [String] {
+    match vm_type {
+        VmType::Tally => &SAFE_TALLY_IMPORTS,
+        VmType::DataRequest => &SAFE_DATA_REQUEST_IMPORTS,
+        VmType::Core => &SAFE_CORE_IMPORTS,
+    }
+}
+
 pub fn create_wasm_imports(
     mut store: &mut Store,
     vm_context: &FunctionEnv<VmContext>,
@@ -29,14 +72,17 @@ pub fn create_wasm_imports(
 
     let mut final_imports = Imports::new();
 
-    let (allowed_imports, custom_imports) = match call_data.vm_type {
-        VmType::Tally => {
-            let tally_imports = create_custom_tally_imports(store, vm_context);
+    let allowed_imports = safe_imports(&call_data.vm_type);
+    let custom_imports = match call_data.vm_type {
+        VmType::Tally => create_custom_tally_imports(store, vm_context),
+        VmType::DataRequest => {
+            if ctx.http_fetcher.is_none() {
+                Err(VmHostError::HttpFetcherNotSet)?;
+            }
 
-            (SAFE_TALLY_IMPORTS.to_vec(), tally_imports)
+            create_custom_data_request_imports(store, vm_context)
         }
-        VmType::DataRequest => todo!(),
-        VmType::Core => todo!(),
+        VmType::Core => create_custom_core_imports(store, vm_context),
     };
 
     // Only allow imports that the user defined
@@ -45,7 +91,7 @@ pub fn create_wasm_imports(
 
     for allowed_import in allowed_imports.iter() {
         // "env" is all our custom host imports
-        if let Some(found_export) = custom_imports.get_export("seda_v1", allowed_import) {
+        if let Some(found_export) = custom_imports.get_export(AbiVersion::V1.namespace(), allowed_import) {
             allowed_host_exports.insert(allowed_import.to_string(), found_export);
         } else if let Some(wasi_version) = wasi_version {
             // When we couldn't find a match in our custom import we try WASI imports
@@ -57,7 +103,11 @@ pub fn create_wasm_imports(
         }
     }
 
-    final_imports.register_namespace("seda_v1", allowed_host_exports);
+    // Only the ABI versions the program links against are built
+    for abi_version in imported_abi_versions(wasm_module) {
+        let exports = abi_version.create_exports(store, vm_context, &allowed_host_exports);
+        final_imports.register_namespace(abi_version.namespace(), exports);
+    }
 
     if let Some(wasi_version) = wasi_version {
         // additionally polyfill the "random_get" wasi import
@@ -93,7 +143,7 @@ pub fn create_wasm_imports(
         allowed_wasi_exports.insert("fd_write", fd_write_import_obj(store, vm_context));
 
         // proc_exit is the only one we don't meter since we immidiatly exit after that import is called. Plus the cost
-        // of proc_exit is included in the GAS_STARTUP.
+        // of proc_exit is included in the startup gas.
 
         final_imports.register_namespace(wasi_version.get_namespace_str(), allowed_wasi_exports);
     }
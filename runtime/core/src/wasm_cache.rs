@@ -2,25 +2,158 @@ use std::{
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
 };
 
-use wasmer::{Module, Store};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use wasmer::{
+    wasmparser::{DataKind, Parser, Payload, TypeRef},
+    Module,
+    Store,
+};
 
 use crate::{
     errors::{Result, VmHostError},
     get_version_file_name,
+    keyed_lock::KeyedLock,
     resources_dir::resources_home_dir,
+    vm::WasmEngine,
 };
 
 pub const WASM_CACHE_FOLDER_NAME: &str = "wasm_cache";
 
-fn create_cache_path(sedad_home: &Path, id: &str) -> Result<PathBuf> {
-    let wasm_cache_path = resources_home_dir(sedad_home)
+/// Length of a hex encoded keccak256 digest
+const WASM_CACHE_ID_LENGTH: usize = 64;
+
+const WASM_CACHE_MANIFEST_EXTENSION: &str = "manifest";
+
+lazy_static! {
+    /// One lock per cache ID, so a binary is only compiled once while
+    /// different binaries can still be compiled in parallel.
+    static ref WASM_CACHE_LOCKS: KeyedLock = KeyedLock::default();
+}
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Runs `f` while holding the lock of the cache entry with the given ID.
+pub fn with_wasm_cache_lock<T>(id: &str, f: impl FnOnce() -> T) -> T {
+    WASM_CACHE_LOCKS.with_lock(id, f)
+}
+
+/// Writes to a hidden temporary file in the same directory and renames it into place,
+/// so readers (including other processes) never observe a partially written file.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(
+        ".{file_name}.{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write_result = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+
+    if let Err(err) = write_result.and_then(|_| std::fs::rename(&temp_path, path)) {
+        let _ = std::fs::remove_file(&temp_path);
+        Err(err)?;
+    }
+
+    Ok(())
+}
+
+/// Sizes of a WASM binary that the startup gas is charged for.
+/// They can only be read from the binary, so they are stored with the compiled artifact.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WasmModuleSize {
+    /// Length of the binary
+    pub wasm_bytes:           u64,
+    /// Pages of the memories the module defines or imports, before anything grows them
+    pub initial_memory_pages: u64,
+    /// Bytes copied into memory by the active data segments on instantiation
+    pub data_segment_bytes:   u64,
+}
+
+impl WasmModuleSize {
+    pub fn from_wasm(wasm_binary: &[u8]) -> Result<Self> {
+        let mut module_size = Self {
+            wasm_bytes: wasm_binary.len() as u64,
+            ..Default::default()
+        };
+
+        for payload in Parser::new(0).parse_all(wasm_binary) {
+            match payload? {
+                Payload::ImportSection(imports) => {
+                    for import in imports {
+                        if let TypeRef::Memory(memory) = import?.ty {
+                            module_size.initial_memory_pages += memory.initial;
+                        }
+                    }
+                }
+                Payload::MemorySection(memories) => {
+                    for memory in memories {
+                        module_size.initial_memory_pages += memory?.initial;
+                    }
+                }
+                Payload::DataSection(data_segments) => {
+                    for data in data_segments {
+                        let data = data?;
+                        if matches!(data.kind, DataKind::Active { .. }) {
+                            module_size.data_segment_bytes += data.data.len() as u64;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(module_size)
+    }
+}
+
+/// Sidecar file stored next to every compiled artifact.
+/// Deserializing an artifact is unsafe, so nothing is loaded unless it matches its manifest.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WasmCacheManifest {
+    /// keccak256 of the serialized artifact
+    pub artifact_hash: String,
+    /// Cache ID of the WASM binary the artifact was compiled from
+    pub wasm_hash:     String,
+    /// Describes the engine the artifact was compiled with
+    pub engine_config: String,
+    /// Read from the WASM binary while compiling
+    pub module_size:   WasmModuleSize,
+}
+
+fn manifest_path(wasm_cache_path: &Path) -> PathBuf {
+    wasm_cache_path.with_extension(WASM_CACHE_MANIFEST_EXTENSION)
+}
+
+fn remove_cache_entry(wasm_cache_path: &Path) {
+    let _ = std::fs::remove_file(wasm_cache_path);
+    let _ = std::fs::remove_file(manifest_path(wasm_cache_path));
+}
+
+/// Names the directory of the artifacts compiled with the given engine configuration
+fn engine_config_hash(engine_config: &str) -> String {
+    hex::encode(Keccak256::digest(engine_config))
+}
+
+fn create_cache_path(sedad_home: &Path, wasm_engine: &WasmEngine, engine_config: &str, id: &str) -> Result<PathBuf> {
+    let version_path = resources_home_dir(sedad_home)
         .join(WASM_CACHE_FOLDER_NAME)
         .join(get_version_file_name());
+    let engine_path = version_path.join(wasm_engine.to_string());
+    let wasm_cache_path = engine_path.join(engine_config_hash(engine_config));
 
     if !wasm_cache_path.exists() {
         std::fs::create_dir_all(&wasm_cache_path)?;
+        remove_legacy_cache_entries(&version_path);
+        remove_legacy_cache_entries(&engine_path);
     }
 
     if wasm_cache_path.exists() && !wasm_cache_path.is_dir() {
@@ -30,20 +163,88 @@ fn create_cache_path(sedad_home: &Path, id: &str) -> Result<PathBuf> {
     Ok(wasm_cache_path.join(id))
 }
 
+/// Older releases stored their artifacts directly in the version directory (some keyed
+/// with a 64-bit seahash) or in the engine directory, those entries can never be looked up
+/// again. They are cleaned up once when an engine configuration directory is created and on every
+/// [`prune_wasm_cache`].
+fn remove_legacy_cache_entries(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+            tracing::debug!("Removing legacy WASM cache entry {:?}", entry.file_name());
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// The cache ID is the hex encoded keccak256 hash of the binary, which is the same
+/// as the program ID on seda-chain.
 pub fn wasm_cache_id<T: AsRef<[u8]>>(wasm_binary: T) -> String {
-    seahash::hash(wasm_binary.as_ref()).to_string()
+    hex::encode(Keccak256::digest(wasm_binary.as_ref()))
 }
 
-pub fn get_full_wasm_path_from_id(sedad_home: &Path, id: &str) -> PathBuf {
+/// Cache IDs end up in a file path, so only accept what [`wasm_cache_id`] can produce
+pub fn is_well_formed_wasm_cache_id(id: &str) -> bool {
+    id.len() == WASM_CACHE_ID_LENGTH && id.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+/// Artifacts are partitioned per engine and per engine configuration (by its hash), since the same
+/// binary compiles to different code and the operator costs and memory limit are compiled into the
+/// artifact. Configurations never share a path, so they cannot overwrite each other's artifacts.
+pub fn get_full_wasm_path_from_id(
+    sedad_home: &Path,
+    wasm_engine: &WasmEngine,
+    engine_config: &str,
+    id: &str,
+) -> PathBuf {
     resources_home_dir(sedad_home)
         .join(WASM_CACHE_FOLDER_NAME)
         .join(get_version_file_name())
+        .join(wasm_engine.to_string())
+        .join(engine_config_hash(engine_config))
         .join(id)
 }
 
 pub fn valid_wasm_cache_id(wasm_cache_path: &Path) -> bool {
-    let version_dir = wasm_cache_path
-        .parent()
+    let is_well_formed = wasm_cache_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .is_some_and(is_well_formed_wasm_cache_id);
+
+    if !is_well_formed {
+        return false;
+    }
+
+    // Engine configuration hashes have the same shape as cache IDs
+    let engine_config_dir = wasm_cache_path.parent();
+    let is_engine_config_dir = engine_config_dir
+        .and_then(|p| p.file_name())
+        .and_then(|f| f.to_str())
+        .is_some_and(is_well_formed_wasm_cache_id);
+
+    if !is_engine_config_dir {
+        return false;
+    }
+
+    let engine_dir = engine_config_dir.and_then(|p| p.parent());
+    let is_engine_dir = engine_dir
+        .and_then(|p| p.file_name())
+        .and_then(|f| f.to_str())
+        .is_some_and(|name| {
+            [WasmEngine::Singlepass, WasmEngine::Cranelift]
+                .iter()
+                .any(|e| e.to_string() == name)
+        });
+
+    if !is_engine_dir {
+        return false;
+    }
+
+    let version_dir = engine_dir
+        .and_then(|p| p.parent())
         .and_then(|p| p.file_name())
         .and_then(|f| f.to_str());
 
@@ -58,31 +259,240 @@ pub fn wasm_cache_store<T: AsRef<[u8]>>(
     sedad_home: &Path,
     compile_store: &Store,
     store: &Store,
+    wasm_engine: &WasmEngine,
     id: &str,
+    engine_config: &str,
     wasm_binary: T,
-) -> Result<Module> {
-    let wasm_cache_path = create_cache_path(sedad_home, id)?;
+) -> Result<(Module, WasmModuleSize)> {
+    let wasm_cache_path = create_cache_path(sedad_home, wasm_engine, engine_config, id)?;
     let module = Module::new(&compile_store, &wasm_binary)?;
+    let module_size = WasmModuleSize::from_wasm(wasm_binary.as_ref())?;
 
-    let mut file = File::create(&wasm_cache_path)?;
     let buffer = module.serialize()?;
-    file.write_all(&buffer)?;
+    write_atomically(&wasm_cache_path, &buffer)?;
     drop(module);
 
-    let wasm_module = unsafe { Module::deserialize_from_file(&store, &wasm_cache_path)? };
-    Ok(wasm_module)
+    let manifest = WasmCacheManifest {
+        artifact_hash: hex::encode(Keccak256::digest(&buffer)),
+        wasm_hash: id.to_string(),
+        engine_config: engine_config.to_string(),
+        module_size,
+    };
+    // The manifest goes last, until it is in place the artifact will not be loaded
+    write_atomically(
+        &manifest_path(&wasm_cache_path),
+        &serde_json::to_vec(&manifest).map_err(VmHostError::from)?,
+    )?;
+
+    let wasm_module = unsafe { Module::deserialize(&store, buffer)? };
+    Ok((wasm_module, module_size))
+}
+
+/// Why a cache entry can not be loaded
+enum CacheEntryRejection {
+    /// The entry is intact but was compiled with another configuration, only possible if two
+    /// configurations hash to the same directory. It is left in place
+    OtherEngineConfig,
+    /// The entry is incomplete or does not match its manifest, it is removed
+    Invalid,
+}
+
+/// Reads the artifact from disk, but only if it matches its manifest
+fn read_verified_artifact(
+    wasm_cache_path: &Path,
+    engine_config: &str,
+) -> Result<(Vec<u8>, WasmModuleSize), CacheEntryRejection> {
+    let manifest = std::fs::read(manifest_path(wasm_cache_path)).map_err(|_| CacheEntryRejection::Invalid)?;
+    let manifest: WasmCacheManifest = serde_json::from_slice(&manifest).map_err(|_| CacheEntryRejection::Invalid)?;
+
+    let wasm_id = wasm_cache_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or(CacheEntryRejection::Invalid)?;
+    if manifest.wasm_hash != wasm_id {
+        tracing::warn!("WASM cache manifest of {wasm_id} does not match, recompiling");
+        return Err(CacheEntryRejection::Invalid);
+    }
+
+    if manifest.engine_config != engine_config {
+        tracing::debug!("WASM cache entry of {wasm_id} was compiled with another engine configuration");
+        return Err(CacheEntryRejection::OtherEngineConfig);
+    }
+
+    let artifact = std::fs::read(wasm_cache_path).map_err(|_| CacheEntryRejection::Invalid)?;
+    if manifest.artifact_hash != hex::encode(Keccak256::digest(&artifact)) {
+        tracing::warn!("WASM cache artifact of {wasm_id} does not match its checksum, recompiling");
+        return Err(CacheEntryRejection::Invalid);
+    }
+
+    Ok((artifact, manifest.module_size))
 }
 
-pub fn wasm_cache_load(store: &Store, wasm_cache_path: &Path) -> Result<Module> {
-    unsafe {
-        let ret = Module::deserialize_from_file(&store, wasm_cache_path);
+/// Loads a compiled artifact from the cache.
+///
+/// Returns `None` when the entry was compiled with another engine configuration, which is a
+/// plain miss, or when it has no manifest, does not match it or fails to deserialize, the
+/// entry is then removed. Either way the caller should compile it again.
+pub fn wasm_cache_load(
+    store: &Store,
+    wasm_cache_path: &Path,
+    engine_config: &str,
+) -> Result<Option<(Module, WasmModuleSize)>> {
+    let (artifact, module_size) = match read_verified_artifact(wasm_cache_path, engine_config) {
+        Ok(verified) => verified,
+        Err(CacheEntryRejection::OtherEngineConfig) => return Ok(None),
+        Err(CacheEntryRejection::Invalid) => {
+            remove_cache_entry(wasm_cache_path);
+            return Ok(None);
+        }
+    };
 
-        if ret.is_err() {
+    match unsafe { Module::deserialize(&store, artifact) } {
+        Ok(module) => {
+            touch_cache_entry(wasm_cache_path);
+            Ok(Some((module, module_size)))
+        }
+        Err(err) => {
             // If an error occurs while deserializing then we can not trust it anymore
-            // so delete the cache file
-            let _ = std::fs::remove_file(wasm_cache_path);
+            // so delete the cache file and let the caller compile it again
+            tracing::warn!("Failed to deserialize WASM cache entry, recompiling: {err}");
+            remove_cache_entry(wasm_cache_path);
+            Ok(None)
         }
+    }
+}
+
+/// Access times are unreliable across filesystems, so the modification time of the
+/// artifact is bumped on every use, including hits in the in-memory module cache,
+/// and used as the last access for [`prune_wasm_cache`].
+pub(crate) fn touch_cache_entry(wasm_cache_path: &Path) {
+    let touched = File::options()
+        .append(true)
+        .open(wasm_cache_path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
 
-        Ok(ret?)
+    if let Err(err) = touched {
+        tracing::debug!(
+            "Failed to update the last access of {}: {err}",
+            wasm_cache_path.display()
+        );
     }
 }
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WasmCachePruneReport {
+    /// Cache directories of other library versions that were removed
+    pub removed_versions: usize,
+    /// Compiled artifacts evicted to get below the maximum size
+    pub evicted_entries:  usize,
+    /// Size in bytes of the cache for the current version after pruning
+    pub cache_size:       u64,
+}
+
+struct CacheEntry {
+    path:      PathBuf,
+    size:      u64,
+    last_used: SystemTime,
+}
+
+fn collect_cache_entries(engine_config_dir: &Path, entries: &mut Vec<CacheEntry>) -> Result<()> {
+    for entry in std::fs::read_dir(engine_config_dir)?.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else {
+            continue;
+        };
+
+        // Manifests are accounted for with their artifact, unless the artifact is gone
+        if let Some(id) = file_name.strip_suffix(&format!(".{WASM_CACHE_MANIFEST_EXTENSION}")) {
+            if is_well_formed_wasm_cache_id(id) && !engine_config_dir.join(id).exists() {
+                let _ = std::fs::remove_file(&path);
+            }
+            continue;
+        }
+
+        if !is_well_formed_wasm_cache_id(file_name) {
+            continue;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let manifest_size = std::fs::metadata(manifest_path(&path)).map_or(0, |m| m.len());
+
+        entries.push(CacheEntry {
+            size: metadata.len() + manifest_size,
+            last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+        });
+    }
+
+    Ok(())
+}
+
+/// Removes the cache directories of other library versions and evicts the least recently
+/// used artifacts until the cache for the current version fits in `max_cache_size` bytes.
+/// A `max_cache_size` of 0 disables the eviction.
+pub fn prune_wasm_cache(sedad_home: &Path, max_cache_size: u64) -> Result<WasmCachePruneReport> {
+    let mut report = WasmCachePruneReport::default();
+
+    let wasm_cache_dir = resources_home_dir(sedad_home).join(WASM_CACHE_FOLDER_NAME);
+    if !wasm_cache_dir.is_dir() {
+        return Ok(report);
+    }
+
+    let current_version = get_version_file_name();
+    for entry in std::fs::read_dir(&wasm_cache_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            tracing::warn!("Illegal file in WASM cache directory: {:?}", entry.file_name());
+            continue;
+        }
+
+        if entry.file_name() != current_version {
+            tracing::info!("Removing WASM cache directory of old version {:?}", entry.file_name());
+            std::fs::remove_dir_all(entry.path())?;
+            report.removed_versions += 1;
+        }
+    }
+
+    let version_dir = wasm_cache_dir.join(current_version);
+    if !version_dir.is_dir() {
+        return Ok(report);
+    }
+
+    remove_legacy_cache_entries(&version_dir);
+
+    let mut entries = Vec::new();
+    for engine_dir in std::fs::read_dir(&version_dir)?.flatten() {
+        if !engine_dir.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+
+        remove_legacy_cache_entries(&engine_dir.path());
+
+        for engine_config_dir in std::fs::read_dir(engine_dir.path())?.flatten() {
+            if engine_config_dir.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                collect_cache_entries(&engine_config_dir.path(), &mut entries)?;
+            }
+        }
+    }
+
+    report.cache_size = entries.iter().map(|entry| entry.size).sum();
+    if max_cache_size == 0 || report.cache_size <= max_cache_size {
+        return Ok(report);
+    }
+
+    entries.sort_by_key(|entry| entry.last_used);
+    for entry in entries {
+        if report.cache_size <= max_cache_size {
+            break;
+        }
+
+        let id = entry.path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+        with_wasm_cache_lock(id, || remove_cache_entry(&entry.path));
+        report.cache_size -= entry.size;
+        report.evicted_entries += 1;
+    }
+
+    Ok(report)
+}
@@ -29,8 +29,11 @@ use wasmer::{
 };
 use wasmer_types::{GlobalIndex, ModuleInfo};
 
+/// Function that maps an operator to the cost of a single unit of its runtime count operand.
+type UnitCostFunction = dyn Fn(&Operator) -> u64 + Send + Sync;
+
 #[derive(Clone)]
-struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex);
+struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex, GlobalIndex);
 
 impl MeteringGlobalIndexes {
     /// The global index in the current module for remaining points.
@@ -46,6 +49,11 @@ impl MeteringGlobalIndexes {
     fn points_exhausted(&self) -> GlobalIndex {
         self.1
     }
+
+    /// The global index in the current module that holds the count operand of an operator while it is being charged.
+    fn count_operand(&self) -> GlobalIndex {
+        self.2
+    }
 }
 
 impl fmt::Debug for MeteringGlobalIndexes {
@@ -53,6 +61,7 @@ impl fmt::Debug for MeteringGlobalIndexes {
         f.debug_struct("MeteringGlobalIndexes")
             .field("remaining_points", &self.remaining_points())
             .field("points_exhausted", &self.points_exhausted())
+            .field("count_operand", &self.count_operand())
             .finish()
     }
 }
@@ -98,6 +107,9 @@ pub struct Metering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
+    /// Function that maps operators with a count operand to the cost of each unit, charged at runtime.
+    unit_cost_function: Option<Arc<UnitCostFunction>>,
+
     /// The global indexes for metering points.
     global_indexes: Mutex<Option<MeteringGlobalIndexes>>,
 }
@@ -107,6 +119,9 @@ pub struct FunctionMetering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
+    /// Function that maps operators with a count operand to the cost of each unit, charged at runtime.
+    unit_cost_function: Option<Arc<UnitCostFunction>>,
+
     /// The global indexes for metering points.
     global_indexes: MeteringGlobalIndexes,
 
@@ -143,9 +158,21 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> Metering<F> {
         Self {
             initial_limit,
             cost_function: Arc::new(cost_function),
+            unit_cost_function: None,
             global_indexes: Mutex::new(None),
         }
     }
+
+    /// Charges operators that have a count operand, like the number of pages of `memory.grow`, for every unit they
+    /// get at runtime. The function returns the cost of a single unit, operators it returns 0 for are only charged
+    /// by the regular cost function. See [`has_count_operand`] for the operators this applies to.
+    pub fn with_unit_cost_function(
+        mut self,
+        unit_cost_function: impl Fn(&Operator) -> u64 + Send + Sync + 'static,
+    ) -> Self {
+        self.unit_cost_function = Some(Arc::new(unit_cost_function));
+        self
+    }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
@@ -153,6 +180,10 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
         f.debug_struct("Metering")
             .field("initial_limit", &self.initial_limit)
             .field("cost_function", &"<function>")
+            .field(
+                "unit_cost_function",
+                &self.unit_cost_function.as_ref().map(|_| "<function>"),
+            )
             .field("global_indexes", &self.global_indexes)
             .finish()
     }
@@ -162,9 +193,10 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
     /// Generates a `FunctionMiddleware` for a given function.
     fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
         Box::new(FunctionMetering {
-            cost_function:    self.cost_function.clone(),
-            global_indexes:   self.global_indexes.lock().unwrap().clone().unwrap(),
-            accumulated_cost: 0,
+            cost_function:      self.cost_function.clone(),
+            unit_cost_function: self.unit_cost_function.clone(),
+            global_indexes:     self.global_indexes.lock().unwrap().clone().unwrap(),
+            accumulated_cost:   0,
         })
     }
 
@@ -198,9 +230,15 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
             ExportIndex::Global(points_exhausted_global_index),
         );
 
+        // Append a scratch global for the count operand of the operator being charged, it is not exported.
+        let count_operand_global_index = module_info.globals.push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info.global_initializers.push(GlobalInit::I32Const(0));
+
         *global_indexes = Some(MeteringGlobalIndexes(
             remaining_points_global_index,
             points_exhausted_global_index,
+            count_operand_global_index,
         ));
 
         Ok(())
@@ -243,15 +281,94 @@ pub fn is_accounting(operator: &Operator) -> bool {
     )
 }
 
+/// Returns `true` if the given operator takes a count operand on top of the stack, which decides how much work it
+/// does at runtime.
+pub fn has_count_operand(operator: &Operator) -> bool {
+    matches!(
+        operator,
+        Operator::MemoryGrow { .. } // number of pages
+            // bulk memory proposal
+            | Operator::MemoryFill { .. } // number of bytes
+            | Operator::MemoryCopy { .. } // number of bytes
+            | Operator::MemoryInit { .. } // number of bytes
+            // reference types proposal
+            | Operator::TableGrow { .. } // number of elements
+            | Operator::TableFill { .. } // number of elements
+            | Operator::TableCopy { .. } // number of elements
+            | Operator::TableInit { .. } // number of elements
+    )
+}
+
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionMetering<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FunctionMetering")
             .field("cost_function", &"<function>")
+            .field(
+                "unit_cost_function",
+                &self.unit_cost_function.as_ref().map(|_| "<function>"),
+            )
             .field("global_indexes", &self.global_indexes)
             .finish()
     }
 }
 
+impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMetering<F> {
+    /// Charges the count operand on top of the stack at `unit_cost` per unit and leaves the operand in place.
+    fn charge_count_operand(&self, unit_cost: u64, state: &mut MiddlewareReaderState<'_>) {
+        let remaining_points = self.global_indexes.remaining_points().as_u32();
+        let count_operand = self.global_indexes.count_operand().as_u32();
+
+        state.extend(&[
+            Operator::GlobalSet {
+                global_index: count_operand,
+            },
+            // if unsigned(count) > unsigned(globals[remaining_points_index]) / unit_cost { throw(); }
+            // Dividing the remaining points keeps the check from overflowing.
+            Operator::GlobalGet {
+                global_index: count_operand,
+            },
+            Operator::I64ExtendI32U,
+            Operator::GlobalGet {
+                global_index: remaining_points,
+            },
+            Operator::I64Const {
+                value: unit_cost as i64,
+            },
+            Operator::I64DivU,
+            Operator::I64GtU,
+            Operator::If {
+                blockty: WpTypeOrFuncType::Empty,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.points_exhausted().as_u32(),
+            },
+            Operator::Unreachable,
+            Operator::End,
+            // globals[remaining_points_index] -= count * unit_cost;
+            Operator::GlobalGet {
+                global_index: remaining_points,
+            },
+            Operator::GlobalGet {
+                global_index: count_operand,
+            },
+            Operator::I64ExtendI32U,
+            Operator::I64Const {
+                value: unit_cost as i64,
+            },
+            Operator::I64Mul,
+            Operator::I64Sub,
+            Operator::GlobalSet {
+                global_index: remaining_points,
+            },
+            // Put the count back for the operator itself
+            Operator::GlobalGet {
+                global_index: count_operand,
+            },
+        ]);
+    }
+}
+
 impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMetering<F> {
     fn feed<'a>(
         &mut self,
@@ -298,6 +415,17 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
 
             self.accumulated_cost = 0;
         }
+
+        if has_count_operand(&operator) {
+            let unit_cost = self
+                .unit_cost_function
+                .as_ref()
+                .map_or(0, |unit_cost_function| unit_cost_function(&operator));
+
+            if unit_cost > 0 {
+                self.charge_count_operand(unit_cost, state);
+            }
+        }
         state.push_operator(operator);
 
         Ok(())
@@ -599,4 +727,69 @@ mod tests {
 
         assert_eq!(get_remaining_points(&mut store, &instance), MeteringPoints::Exhausted);
     }
+
+    #[test]
+    fn metering_charges_count_operands() {
+        let bytecode = wat2wasm(
+            br#"(module
+            (memory 1)
+            (func $grow_f (param $pages i32) (result i32)
+                local.get $pages
+                memory.grow)
+            (func $fill_f (param $bytes i32)
+                i32.const 0
+                i32.const 1
+                local.get $bytes
+                memory.fill)
+            (export "grow" (func $grow_f))
+            (export "fill" (func $fill_f))
+        )"#,
+        )
+        .unwrap();
+
+        let metering = Arc::new(
+            Metering::new(100, cost_function).with_unit_cost_function(|operator: &Operator| match operator {
+                Operator::MemoryGrow { .. } => 10,
+                Operator::MemoryFill { .. } => 1,
+                _ => 0,
+            }),
+        );
+        let mut compiler_config = Singlepass::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let grow: TypedFunction<i32, i32> = instance.exports.get_function("grow").unwrap().typed(&store).unwrap();
+
+        // 1 point for `local.get $pages` and 10 points for each page
+        assert_eq!(grow.call(&mut store, 2).unwrap(), 1);
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(79)
+        );
+
+        assert_eq!(grow.call(&mut store, 0).unwrap(), 3);
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(78)
+        );
+
+        // The memory does not grow when the pages can't be paid for
+        assert!(grow.call(&mut store, 8).is_err());
+        assert_eq!(get_remaining_points(&mut store, &instance), MeteringPoints::Exhausted);
+        set_remaining_points(&mut store, &instance, 100);
+        assert_eq!(grow.call(&mut store, 0).unwrap(), 3);
+
+        // 3 points for the `i32.const`s and `local.get $bytes`, 1 point for each byte
+        let fill: TypedFunction<i32, ()> = instance.exports.get_function("fill").unwrap().typed(&store).unwrap();
+        fill.call(&mut store, 50).unwrap();
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(46)
+        );
+
+        assert!(fill.call(&mut store, 47).is_err());
+        assert_eq!(get_remaining_points(&mut store, &instance), MeteringPoints::Exhausted);
+    }
 }
@@ -4,9 +4,8 @@ package tallyvm
 import "C"
 
 import (
+	"errors"
 	"fmt"
-	"os"
-	"path/filepath"
 	"runtime"
 	"sync"
 	"unsafe"
@@ -20,12 +19,16 @@ type ExitInfo struct {
 }
 
 type VmResult struct {
-	Stdout    []string
-	Stderr    []string
-	Result    *[]byte
-	ResultLen int
-	ExitInfo  ExitInfo
-	GasUsed   uint64
+	Stdout     []string
+	Stderr     []string
+	Result     *[]byte
+	ResultLen  int
+	ExitInfo   ExitInfo
+	GasUsed    uint64
+	// CallId is the call ID of the request, empty when it did not have one
+	CallId     string
+	// AbiVersion is the host ABI version the program was built against, 0 if it uses no SEDA host imports
+	AbiVersion uint32
 }
 
 var TallyVmDir string
@@ -33,15 +36,52 @@ var TallyMaxBytes uint = 1024
 var TallyMaxStdoutBytes uint = 512
 var TallyMaxStderrBytes uint = 512
 
-func buildVmSettings() (C.FfiVmSettings, *C.char) {
+// TallyWasmEngine selects the compiler, Cranelift should only be used off-chain
+var TallyWasmEngine WasmEngine = WasmEngineSinglepass
+
+// TallyGasSchedules is the JSON encoded list of gas schedule versions set by
+// chain governance, empty only registers the default prices of the VM
+var TallyGasSchedules string
+
+type WasmEngine int
+
+const (
+	WasmEngineSinglepass WasmEngine = iota
+	WasmEngineCranelift
+)
+
+func (e WasmEngine) toC() C.FfiWasmEngine {
+	if e == WasmEngineCranelift {
+		return C.Cranelift
+	}
+	return C.Singlepass
+}
+
+// buildVmSettings returns the settings along with a function that frees the
+// C strings they point to.
+func buildVmSettings() (C.FfiVmSettings, func()) {
 	// convert config dir to C string
 	tallyVmDirC := C.CString(TallyVmDir)
-	return C.FfiVmSettings{
+
+	// nil makes the VM register only its default prices
+	var gasSchedulesC *C.char
+	if TallyGasSchedules != "" {
+		gasSchedulesC = C.CString(TallyGasSchedules)
+	}
+
+	settings := C.FfiVmSettings{
 		sedad_home:       tallyVmDirC,
 		max_result_bytes: C.uintptr_t(TallyMaxBytes),
 		stdout_limit:     C.uintptr_t(TallyMaxStdoutBytes),
 		stderr_limit:     C.uintptr_t(TallyMaxStderrBytes),
-	}, tallyVmDirC
+		wasm_engine:      TallyWasmEngine.toC(),
+		gas_schedules:    gasSchedulesC,
+	}
+
+	return settings, func() {
+		C.free(unsafe.Pointer(tallyVmDirC))
+		C.free(unsafe.Pointer(gasSchedulesC))
+	}
 }
 
 type cRequest struct {
@@ -115,6 +155,18 @@ func newCRequest(
 	}
 }
 
+// setCallId queues the request behind others with the same ID, an empty ID
+// leaves it unset so the request runs immediately.
+func (r *cRequest) setCallId(callId string) {
+	if callId == "" {
+		return
+	}
+
+	callIdC := C.CString(callId)
+	r.frees = append(r.frees, callIdC)
+	r.req.call_id = callIdC
+}
+
 func (r *cRequest) cleanup() {
 	for _, p := range r.frees {
 		C.free(unsafe.Pointer(p))
@@ -164,7 +216,9 @@ func buildResultFromC(cResult *C.FfiVmResult) VmResult {
 			ExitMessage: exitMessage,
 			ExitCode:    exitCode,
 		},
-		GasUsed: uint64(cResult.gas_used),
+		GasUsed:    uint64(cResult.gas_used),
+		CallId:     C.GoString(cResult.call_id),
+		AbiVersion: uint32(cResult.abi_version),
 	}
 }
 
@@ -173,8 +227,8 @@ func ExecuteTallyVm(
 	args []string,
 	envs map[string]string,
 ) VmResult {
-	cSettings, configDirC := buildVmSettings()
-	defer C.free(unsafe.Pointer(configDirC))
+	cSettings, freeSettings := buildVmSettings()
+	defer freeSettings()
 
 	cr := newCRequest(bytes, args, envs)
 	defer cr.cleanup()
@@ -184,6 +238,101 @@ func ExecuteTallyVm(
 	return buildResultFromC(&result)
 }
 
+// ExecuteTallyVmAtHeight runs a program with the gas schedule version that was
+// active at the given block height, so replayed blocks are charged the same gas.
+func ExecuteTallyVmAtHeight(
+	bytes []byte,
+	args []string,
+	envs map[string]string,
+	blockHeight uint64,
+) VmResult {
+	cSettings, freeSettings := buildVmSettings()
+	defer freeSettings()
+
+	cr := newCRequest(bytes, args, envs)
+	defer cr.cleanup()
+	cr.req.block_height = C.uint64_t(blockHeight)
+
+	result := C.execute_tally_request(cSettings, cr.req)
+	return buildResultFromC(&result)
+}
+
+// ExecuteTallyVmFromCache runs a program that was already compiled into the
+// WASM cache, without sending the binary across the FFI boundary.
+func ExecuteTallyVmFromCache(
+	wasmId string,
+	args []string,
+	envs map[string]string,
+) VmResult {
+	cSettings, freeSettings := buildVmSettings()
+	defer freeSettings()
+
+	cr := newCRequest(nil, args, envs)
+	defer cr.cleanup()
+
+	wasmIdC := C.CString(wasmId)
+	cr.frees = append(cr.frees, wasmIdC)
+	cr.req.wasm_id = wasmIdC
+
+	result := C.execute_tally_request(cSettings, cr.req)
+	return buildResultFromC(&result)
+}
+
+type CompatibilityReport struct {
+	WasmId              string
+	DisallowedImports   []string
+	MissingMemoryExport bool
+	MissingStartExport  bool
+	MemoryTooLarge      bool
+}
+
+// IsCompatible reports whether the program can be executed by the VM.
+func (r CompatibilityReport) IsCompatible() bool {
+	return len(r.DisallowedImports) == 0 && !r.MissingMemoryExport && !r.MissingStartExport && !r.MemoryTooLarge
+}
+
+// CompileWasmProgram compiles a program into the WASM cache ahead of its first
+// execution and reports everything that would keep it from running.
+func CompileWasmProgram(bytes []byte) (CompatibilityReport, error) {
+	return CompileWasmProgramAtHeight(bytes, 0)
+}
+
+// CompileWasmProgramAtHeight compiles a program for the gas schedule version that
+// is active at the given block height, executions at that height use the artifact.
+func CompileWasmProgramAtHeight(bytes []byte, blockHeight uint64) (CompatibilityReport, error) {
+	cSettings, freeSettings := buildVmSettings()
+	defer freeSettings()
+
+	var wasmBytesPtr *C.uint8_t
+	if len(bytes) > 0 {
+		wasmBytesPtr = (*C.uint8_t)(unsafe.Pointer(&bytes[0]))
+	}
+
+	cResult := C.compile_wasm_program(cSettings, wasmBytesPtr, C.uintptr_t(len(bytes)), nil, C.uint64_t(blockHeight))
+	defer C.free_ffi_compile_result(&cResult)
+
+	if cResult.error_message != nil {
+		return CompatibilityReport{}, errors.New(C.GoString(cResult.error_message))
+	}
+
+	importsLen := int(cResult.disallowed_imports_len)
+	disallowedImports := make([]string, importsLen)
+	if importsLen > 0 {
+		cs := (*[1 << 30]*C.char)(unsafe.Pointer(cResult.disallowed_imports_ptr))[:importsLen:importsLen]
+		for i, cstr := range cs {
+			disallowedImports[i] = C.GoString(cstr)
+		}
+	}
+
+	return CompatibilityReport{
+		WasmId:              C.GoString(cResult.wasm_id),
+		DisallowedImports:   disallowedImports,
+		MissingMemoryExport: bool(cResult.missing_memory_export),
+		MissingStartExport:  bool(cResult.missing_start_export),
+		MemoryTooLarge:      bool(cResult.memory_too_large),
+	}, nil
+}
+
 func ExecuteMultipleFromGoInParallel(
 	bytes [][]byte,
 	args [][]string,
@@ -218,49 +367,57 @@ func ExecuteMultipleFromGoInParallel(
 }
 
 func ExecuteMultipleFromC(bytes [][]byte, args [][]string, envs []map[string]string) []VmResult {
-	cSettings, configDirC := buildVmSettings()
-	defer C.free(unsafe.Pointer(configDirC))
-
 	cReqs := make([]cRequest, len(bytes))
 	for i := range bytes {
 		cReqs[i] = newCRequest(bytes[i], args[i], envs[i])
 		defer cReqs[i].cleanup()
 	}
 
-	count := len(cReqs)
-	size := C.uintptr_t(count) * C.uintptr_t(unsafe.Sizeof(cReqs[0].req))
-	cArray := C.malloc(size)
-	defer C.free(cArray)
-	arr := (*[1 << 28]C.FfiTallyRequest)(cArray)
-	for i := 0; i < count; i++ {
-		arr[i] = cReqs[i].req
-	}
-
-	cResults := C.execute_tally_requests(
-		cSettings,
-		(*C.FfiTallyRequest)(cArray),
-		C.uintptr_t(count),
-	)
-
-	results := make([]VmResult, count)
-	slice := (*[1 << 30]C.FfiVmResult)(unsafe.Pointer(cResults))[:count:count]
-	for i := 0; i < count; i++ {
-		results[i] = buildResultFromC(&slice[i])
-	}
-	return results
+	return executeCRequests(cReqs, false)
 }
 
 func ExecuteMultipleFromCParallel(bytes [][]byte, args [][]string, envs []map[string]string) []VmResult {
-	cSettings, configDirC := buildVmSettings()
-	defer C.free(unsafe.Pointer(configDirC))
-
 	cReqs := make([]cRequest, len(bytes))
 	for i := range bytes {
 		cReqs[i] = newCRequest(bytes[i], args[i], envs[i])
 		defer cReqs[i].cleanup()
 	}
 
+	return executeCRequests(cReqs, true)
+}
+
+// TallyRequest is a single execution of a batch. Requests sharing a CallId run
+// one after the other, an empty CallId runs immediately and is not reported back
+// on the result.
+type TallyRequest struct {
+	Bytes  []byte
+	Args   []string
+	Envs   map[string]string
+	CallId string
+}
+
+// ExecuteTallyRequests runs the requests in parallel, the results are in the
+// same order as the requests and carry their CallId.
+func ExecuteTallyRequests(requests []TallyRequest) []VmResult {
+	cReqs := make([]cRequest, len(requests))
+	for i, request := range requests {
+		cReqs[i] = newCRequest(request.Bytes, request.Args, request.Envs)
+		cReqs[i].setCallId(request.CallId)
+		defer cReqs[i].cleanup()
+	}
+
+	return executeCRequests(cReqs, true)
+}
+
+func executeCRequests(cReqs []cRequest, parallel bool) []VmResult {
 	count := len(cReqs)
+	if count == 0 {
+		return []VmResult{}
+	}
+
+	cSettings, freeSettings := buildVmSettings()
+	defer freeSettings()
+
 	size := C.uintptr_t(count) * C.uintptr_t(unsafe.Sizeof(cReqs[0].req))
 	cArray := C.malloc(size)
 	defer C.free(cArray)
@@ -269,14 +426,23 @@ func ExecuteMultipleFromCParallel(bytes [][]byte, args [][]string, envs []map[st
 		arr[i] = cReqs[i].req
 	}
 
-	cResults := C.execute_tally_requests_parallel(
-		cSettings,
-		(*C.FfiTallyRequest)(cArray),
-		C.uintptr_t(count),
-	)
+	var cResults unsafe.Pointer
+	if parallel {
+		cResults = unsafe.Pointer(C.execute_tally_requests_parallel(
+			cSettings,
+			(*C.FfiTallyRequest)(cArray),
+			C.uintptr_t(count),
+		))
+	} else {
+		cResults = unsafe.Pointer(C.execute_tally_requests(
+			cSettings,
+			(*C.FfiTallyRequest)(cArray),
+			C.uintptr_t(count),
+		))
+	}
 
 	results := make([]VmResult, count)
-	slice := (*[1 << 30]C.FfiVmResult)(unsafe.Pointer(cResults))[:count:count]
+	slice := (*[1 << 30]C.FfiVmResult)(cResults)[:count:count]
 	for i := 0; i < count; i++ {
 		results[i] = buildResultFromC(&slice[i])
 	}
@@ -295,33 +461,41 @@ func GetInvalidateWasmCacheInfo() (string, string) {
 	return path, currentVersion
 }
 
-func InvalidateWasmCache(ctx sdk.Context) error {
-	path, currentVersion := GetInvalidateWasmCacheInfo()
-	ctx.Logger().Info("Invalidating WASM cache for versions not matching:", currentVersion)
+type WasmCachePruneResult struct {
+	RemovedVersions uint
+	EvictedEntries  uint
+	CacheSize       uint64
+}
 
-	vmVersionDirs, err := os.ReadDir(path)
-	if err != nil {
-		return err
+// PruneWasmCache removes the WASM cache of other VM versions and evicts the least
+// recently used programs until the cache fits in maxCacheSize bytes, 0 means no size limit.
+func PruneWasmCache(maxCacheSize uint64) (WasmCachePruneResult, error) {
+	tallyVmDirC := C.CString(TallyVmDir)
+	defer C.free(unsafe.Pointer(tallyVmDirC))
+
+	cResponse := C.prune_wasm_cache(tallyVmDirC, C.uint64_t(maxCacheSize))
+	defer C.free_ffi_wasm_cache_prune_result(&cResponse)
+
+	if cResponse.error_message != nil {
+		return WasmCachePruneResult{}, errors.New(C.GoString(cResponse.error_message))
 	}
 
-	// loop through the directories, that have the versions as their names
-	for _, vmVersionDir := range vmVersionDirs {
-		if !vmVersionDir.IsDir() {
-			ctx.Logger().Warn("Illegal file/folder in Tally WASM Cache Directory:", vmVersionDir.Name())
-			continue
-		}
+	return WasmCachePruneResult{
+		RemovedVersions: uint(cResponse.removed_versions),
+		EvictedEntries:  uint(cResponse.evicted_entries),
+		CacheSize:       uint64(cResponse.cache_size),
+	}, nil
+}
 
-		// Check if the folder name does not match the current version
-		// if so delete it
-		name := vmVersionDir.Name()
-		if name != currentVersion {
-			ctx.Logger().Info("Removing old Tally WASM Cache Directory:", name)
-			if err := os.RemoveAll(filepath.Join(path, vmVersionDir.Name())); err != nil {
-				return err
-			}
-		}
+func InvalidateWasmCache(ctx sdk.Context) error {
+	_, currentVersion := GetInvalidateWasmCacheInfo()
+	ctx.Logger().Info("Invalidating WASM cache for versions not matching:", currentVersion)
 
+	result, err := PruneWasmCache(0)
+	if err != nil {
+		return err
 	}
 
+	ctx.Logger().Info("Removed old Tally WASM Cache Directories:", result.RemovedVersions)
 	return nil
 }
@@ -9,6 +9,11 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+typedef enum FfiWasmEngine {
+  Singlepass,
+  Cranelift,
+} FfiWasmEngine;
+
 typedef struct FfiExitInfo {
   const char *exit_message;
   int32_t exit_code;
@@ -23,6 +28,11 @@ typedef struct FfiVmResult {
   uintptr_t result_len;
   struct FfiExitInfo exit_info;
   uint64_t gas_used;
+  const char *call_id;
+  /**
+   * Host ABI version the program was built against, 0 if it uses no SEDA host imports
+   */
+  uint32_t abi_version;
 } FfiVmResult;
 
 typedef struct FfiVmSettings {
@@ -30,6 +40,14 @@ typedef struct FfiVmSettings {
   uintptr_t max_result_bytes;
   uintptr_t stdout_limit;
   uintptr_t stderr_limit;
+  /**
+   * Cranelift is only meant for off-chain workloads, on-chain execution uses Singlepass
+   */
+  enum FfiWasmEngine wasm_engine;
+  /**
+   * JSON encoded list of gas schedule versions, null only registers the default prices
+   */
+  const char *gas_schedules;
 } FfiVmSettings;
 
 typedef struct FfiTallyRequest {
@@ -40,13 +58,52 @@ typedef struct FfiTallyRequest {
   const char *const *env_keys_ptr;
   const char *const *env_values_ptr;
   uintptr_t env_count;
+  /**
+   * Executions sharing a call ID run one after the other, null runs immediately
+   */
+  const char *call_id;
+  /**
+   * Name of the gas schedule version to charge, null uses the version active at `block_height`
+   */
+  const char *gas_schedule_version;
+  uint64_t block_height;
+  /**
+   * Cache ID of an already compiled program, when set `wasm_bytes` is ignored
+   */
+  const char *wasm_id;
 } FfiTallyRequest;
 
+typedef struct FfiCompileResult {
+  /**
+   * Cache ID of the compiled program, null when compiling failed
+   */
+  const char *wasm_id;
+  const char *const *disallowed_imports_ptr;
+  uintptr_t disallowed_imports_len;
+  bool missing_memory_export;
+  bool missing_start_export;
+  bool memory_too_large;
+  /**
+   * Null when compiling succeeded
+   */
+  const char *error_message;
+} FfiCompileResult;
+
 typedef struct FfiInvalidateWasmCacheInfo {
   const char *wasm_cache_dirs;
   const char *version_name;
 } FfiInvalidateWasmCacheInfo;
 
+typedef struct FfiWasmCachePruneResult {
+  uintptr_t removed_versions;
+  uintptr_t evicted_entries;
+  uint64_t cache_size;
+  /**
+   * Null when pruning succeeded
+   */
+  const char *error_message;
+} FfiWasmCachePruneResult;
+
 /**
  * # Safety
  */
@@ -77,6 +134,26 @@ const struct FfiVmResult *execute_tally_requests_parallel(struct FfiVmSettings s
                                                           const struct FfiTallyRequest *request,
                                                           uintptr_t count);
 
+/**
+ * # Safety
+ */
+void free_ffi_compile_result(struct FfiCompileResult *compile_result);
+
+/**
+ * Compiles a program ahead of its first execution and stores it in the WASM cache,
+ * the result reports everything that would keep the program from running.
+ * The gas schedule version is selected like for an execution: by name, or when
+ * `gas_schedule_version` is null by `block_height`. Only executions with the same
+ * version are served from the compiled artifact.
+ *
+ * # Safety
+ */
+struct FfiCompileResult compile_wasm_program(struct FfiVmSettings settings,
+                                             const uint8_t *wasm_bytes,
+                                             uintptr_t wasm_bytes_len,
+                                             const char *gas_schedule_version,
+                                             uint64_t block_height);
+
 /**
  * # Safety
  */
@@ -86,3 +163,17 @@ void free_ffi_invalidate_wasm_cache_info(struct FfiInvalidateWasmCacheInfo *cach
  * # Safety
  */
 struct FfiInvalidateWasmCacheInfo invalidate_wasm_cache_info(const char *sedad_home_ptr);
+
+/**
+ * # Safety
+ */
+void free_ffi_wasm_cache_prune_result(struct FfiWasmCachePruneResult *prune_result);
+
+/**
+ * Removes the WASM cache of other library versions and evicts the least recently used
+ * programs until the cache fits in `max_cache_size` bytes, 0 means no size limit.
+ *
+ * # Safety
+ */
+struct FfiWasmCachePruneResult prune_wasm_cache(const char *sedad_home_ptr,
+                                                uint64_t max_cache_size);